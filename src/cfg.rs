@@ -0,0 +1,897 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// The name of the optional file in a sheet folder that overrides options for that sheet.
+pub const SHEET_CFG_FILE: &'static str = "sheet.cfg";
+
+/// The most colors a palette can have, leaving one index of an indexed PNG for transparency.
+pub const MAX_PALETTE_COLORS: u32 = 255;
+
+/// Options that apply to every sheet in a run, so they can't be set in a sheet config file.
+const RUN_OPTIONS: [&'static str; 2] = ["palette", "palette_colors"];
+
+/// Configuration for a run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cfg {
+    pub output_render_pngs: bool,
+    pub sprite_w_px: u32,
+    pub sprite_h_px: u32,
+    pub num_directions: u32,
+    pub packer: PackerKind,
+    pub max_texture_size: Option<u32>,
+    pub dedupe: bool,
+    pub padding_px: u32,
+    pub extrude_px: u32,
+    pub border_px: u32,
+    pub allow_rotation: bool,
+    pub layout: LayoutKind,
+    pub grid_cell: GridCellSize,
+    pub grid_anchor: GridAnchor,
+    pub grid_columns: u32,
+    pub pivot_object: String,
+    pub crop: CropPolicy,
+    pub alpha_threshold: u8,
+    pub alpha_bleed: bool,
+    pub premultiply_alpha: bool,
+    pub palette: Option<String>,
+    pub palette_colors: u32,
+    pub dither: DitherKind,
+    pub render_scale: u32,
+    pub downsample: DownsampleFilter,
+    pub outline: OutlineKind,
+    pub outline_color: [u8; 4],
+    pub outline_thickness: u32,
+    pub outline_corners: OutlineCorners,
+    pub passes: Vec<PassKind>,
+    pub normal_map: Option<bool>,
+    pub team_color_key: Option<[u8; 3]>,
+    pub team_color_tolerance: u32,
+    pub team_colors: Vec<[u8; 3]>,
+    pub shadow: bool,
+    pub shadow_color: [u8; 4],
+    pub shadow_length: u32,
+    pub shadow_skew: i32,
+    pub shadow_blur: u32,
+    pub texture_format: TextureFormat,
+    pub block_compression: BlockCompression,
+    pub mipmaps: bool,
+    pub image_output: ImageOutput,
+    pub exporters: Vec<ExporterKind>,
+}
+
+impl Cfg {
+    /// Creates a new config with the default options.
+    pub fn new(sprite_w_px: u32, sprite_h_px: u32, num_directions: u32) -> Self {
+        Self {
+            output_render_pngs: false,
+            sprite_w_px,
+            sprite_h_px,
+            num_directions,
+            packer: PackerKind::MaxRects,
+            max_texture_size: None,
+            dedupe: true,
+            padding_px: 0,
+            extrude_px: 0,
+            border_px: 0,
+            allow_rotation: false,
+            layout: LayoutKind::Packed,
+            grid_cell: GridCellSize::Max,
+            grid_anchor: GridAnchor::Bottom,
+            grid_columns: 0,
+            pivot_object: "pivot".to_string(),
+            crop: CropPolicy::Frame,
+            alpha_threshold: 0,
+            alpha_bleed: true,
+            premultiply_alpha: false,
+            palette: None,
+            palette_colors: 0,
+            dither: DitherKind::None,
+            render_scale: 1,
+            downsample: DownsampleFilter::Box,
+            outline: OutlineKind::None,
+            outline_color: [0, 0, 0, 255],
+            outline_thickness: 1,
+            outline_corners: OutlineCorners::Square,
+            passes: vec![],
+            normal_map: None,
+            team_color_key: None,
+            team_color_tolerance: 20,
+            team_colors: vec![],
+            shadow: false,
+            shadow_color: [0, 0, 0, 128],
+            shadow_length: 50,
+            shadow_skew: 0,
+            shadow_blur: 2,
+            texture_format: TextureFormat::Png,
+            block_compression: BlockCompression::Bc3,
+            mipmaps: false,
+            image_output: ImageOutput::Embedded,
+            exporters: vec![ExporterKind::Builder],
+        }
+    }
+
+    /// Sets a single named option, as given by `--name=value`.
+    pub fn set_option<'a>(&mut self, name: &'a str, value: &'a str) -> Result<(), String> {
+        match name {
+            "packer" => self.packer = PackerKind::parse(value)?,
+            "max_texture_size" => {
+                // Zero means there's no limit
+                self.max_texture_size = match parse_u32(name, value)? {
+                    0 => None,
+                    size => Some(size),
+                }
+            }
+            "dedupe" => self.dedupe = parse_bool(name, value)?,
+            "padding" => self.padding_px = parse_u32(name, value)?,
+            "extrude" => self.extrude_px = parse_u32(name, value)?,
+            "border" => self.border_px = parse_u32(name, value)?,
+            "allow_rotation" => self.allow_rotation = parse_bool(name, value)?,
+            "layout" => self.layout = LayoutKind::parse(value)?,
+            "grid_cell" => self.grid_cell = GridCellSize::parse(value)?,
+            "grid_anchor" => self.grid_anchor = GridAnchor::parse(value)?,
+            "grid_columns" => self.grid_columns = parse_u32(name, value)?,
+            "pivot_object" => self.pivot_object = value.to_string(),
+            "crop" => self.crop = CropPolicy::parse(value)?,
+            "alpha_threshold" => self.alpha_threshold = parse_u8(name, value)?,
+            "alpha_bleed" => self.alpha_bleed = parse_bool(name, value)?,
+            "premultiply_alpha" => self.premultiply_alpha = parse_bool(name, value)?,
+            "palette" => {
+                // Empty means there's no palette file
+                self.palette = match value {
+                    "" => None,
+                    path => Some(path.to_string()),
+                }
+            }
+            "palette_colors" => {
+                let colors = parse_u32(name, value)?;
+                if colors > MAX_PALETTE_COLORS {
+                    return Err(format!(
+                        "Option '{}' expects at most {} colors, got '{}'.",
+                        name, MAX_PALETTE_COLORS, value
+                    ));
+                }
+
+                self.palette_colors = colors;
+            }
+            "dither" => self.dither = DitherKind::parse(value)?,
+            "render_scale" => {
+                self.render_scale = match parse_u32(name, value)? {
+                    0 => return Err(format!("Option '{}' must be at least 1.", name)),
+                    scale => scale,
+                }
+            }
+            "downsample" => self.downsample = DownsampleFilter::parse(value)?,
+            "outline" => self.outline = OutlineKind::parse(value)?,
+            "outline_color" => self.outline_color = parse_color(name, value)?,
+            "outline_thickness" => self.outline_thickness = parse_u32(name, value)?,
+            "outline_corners" => self.outline_corners = OutlineCorners::parse(value)?,
+            "passes" => {
+                let mut passes = vec![];
+                for pass in value.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+                    let pass = PassKind::parse(pass)?;
+                    if !passes.contains(&pass) {
+                        passes.push(pass);
+                    }
+                }
+
+                self.passes = passes;
+                self.apply_normal_map();
+            }
+            "team_color_key" => {
+                // Empty means there's no key
+                self.team_color_key = match value {
+                    "" => None,
+                    color => {
+                        let [r, g, b, _] = parse_color(name, color)?;
+                        Some([r, g, b])
+                    }
+                }
+            }
+            "team_color_tolerance" => self.team_color_tolerance = parse_u32(name, value)?,
+            "team_colors" => {
+                let mut team_colors = vec![];
+                for color in value.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+                    let [r, g, b, _] = parse_color(name, color)?;
+                    team_colors.push([r, g, b]);
+                }
+
+                self.team_colors = team_colors;
+            }
+            "shadow" => self.shadow = parse_bool(name, value)?,
+            "shadow_color" => self.shadow_color = parse_color(name, value)?,
+            "shadow_length" => self.shadow_length = parse_u32(name, value)?,
+            "shadow_skew" => self.shadow_skew = parse_i32(name, value)?,
+            "shadow_blur" => self.shadow_blur = parse_u32(name, value)?,
+            "texture_format" => self.texture_format = TextureFormat::parse(value)?,
+            "block_compression" => self.block_compression = BlockCompression::parse(value)?,
+            "mipmaps" => self.mipmaps = parse_bool(name, value)?,
+            "image_output" => self.image_output = ImageOutput::parse(value)?,
+            "exporters" => {
+                let mut exporters = vec![];
+                for exporter in value.split(',').map(|e| e.trim()).filter(|e| !e.is_empty()) {
+                    let exporter = ExporterKind::parse(exporter)?;
+                    if !exporters.contains(&exporter) {
+                        exporters.push(exporter);
+                    }
+                }
+
+                if exporters.is_empty() {
+                    return Err(format!("Option '{}' needs at least one exporter.", name));
+                }
+
+                self.exporters = exporters;
+            }
+            "normal_map" => {
+                // Shorthand for adding or removing the normal pass
+                self.normal_map = Some(parse_bool(name, value)?);
+                self.apply_normal_map();
+            }
+            _ => return Err(format!("Unknown option '{}'.", name)),
+        }
+
+        Ok(())
+    }
+
+    /// Adds or removes the normal pass if `normal_map` was set, so it doesn't matter if `passes` is set before or after it.
+    fn apply_normal_map(&mut self) {
+        if let Some(normal_map) = self.normal_map {
+            self.passes.retain(|p| *p != PassKind::Normal);
+            if normal_map {
+                self.passes.push(PassKind::Normal);
+            }
+        }
+    }
+
+    /// Sets the options in a sheet config file, if it exists.
+    /// Each line is formatted as `name=value`, and lines starting with '#' are ignored.
+    pub fn apply_file(&mut self, path: &Path) -> Result<(), String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut split = line.splitn(2, '=');
+            let name = split.next().unwrap_or_default().trim();
+            let value = split.next().unwrap_or_default().trim();
+
+            if RUN_OPTIONS.contains(&name) {
+                return Err(format!(
+                    "Option '{}' applies to every sheet, so it can only be set on the command line.",
+                    name
+                ));
+            }
+
+            self.set_option(name, value)?;
+        }
+
+        self.validate()
+    }
+
+    /// Checks that the options work together, once they've all been set.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.allow_rotation && self.exporters.contains(&ExporterKind::Godot) {
+            return Err("Godot's AtlasTexture can't rotate regions, so the godot exporter needs allow_rotation to be false.".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_u32<'a>(name: &'a str, value: &'a str) -> Result<u32, String> {
+    value.parse::<u32>().map_err(|_| {
+        format!(
+            "Option '{}' expects a positive integer, got '{}'.",
+            name, value
+        )
+    })
+}
+
+fn parse_i32<'a>(name: &'a str, value: &'a str) -> Result<i32, String> {
+    value
+        .parse::<i32>()
+        .map_err(|_| format!("Option '{}' expects an integer, got '{}'.", name, value))
+}
+
+fn parse_u8<'a>(name: &'a str, value: &'a str) -> Result<u8, String> {
+    value.parse::<u8>().map_err(|_| {
+        format!(
+            "Option '{}' expects an integer from 0 to 255, got '{}'.",
+            name, value
+        )
+    })
+}
+
+/// Parses a 'rrggbb' or 'rrggbbaa' hex color, optionally starting with '#'.
+fn parse_color<'a>(name: &'a str, value: &'a str) -> Result<[u8; 4], String> {
+    let hex = value.trim_start_matches('#');
+    let channel = |i: usize| {
+        hex.get(i..i + 2)
+            .and_then(|c| u8::from_str_radix(c, 16).ok())
+    };
+
+    let alpha = match hex.len() {
+        6 => Some(255),
+        8 => channel(6),
+        _ => None,
+    };
+
+    match (channel(0), channel(2), channel(4), alpha) {
+        (Some(r), Some(g), Some(b), Some(a)) => Ok([r, g, b, a]),
+        _ => Err(format!(
+            "Option '{}' expects a hex color, got '{}'.",
+            name, value
+        )),
+    }
+}
+
+fn parse_bool<'a>(name: &'a str, value: &'a str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" | "" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(format!(
+            "Option '{}' expects true or false, got '{}'.",
+            name, value
+        )),
+    }
+}
+
+/// The algorithm used to lay out frames in the sheet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PackerKind {
+    Grid,
+    Guillotine,
+    MaxRects,
+    Shelf,
+    Skyline,
+}
+
+impl PackerKind {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "grid" => Ok(Self::Grid),
+            "guillotine" => Ok(Self::Guillotine),
+            "maxrects" => Ok(Self::MaxRects),
+            "shelf" => Ok(Self::Shelf),
+            "skyline" => Ok(Self::Skyline),
+            _ => Err(format!(
+                "Unknown packer '{}'. Expected one of: grid, guillotine, maxrects, shelf, skyline.",
+                value
+            )),
+        }
+    }
+}
+
+/// How frames are placed in the sheet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LayoutKind {
+    /// Frames are packed as tightly as the packer can manage.
+    Packed,
+    /// Every frame gets an identical cell, with a row for each direction.
+    Grid,
+}
+
+impl LayoutKind {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "packed" => Ok(Self::Packed),
+            "grid" => Ok(Self::Grid),
+            _ => Err(format!(
+                "Unknown layout '{}'. Expected one of: packed, grid.",
+                value
+            )),
+        }
+    }
+}
+
+/// The size of each cell in the grid layout.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GridCellSize {
+    /// The largest cropped frame.
+    Max,
+    /// The full render size, with frames placed where they were in the render.
+    Render,
+}
+
+impl GridCellSize {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "max" => Ok(Self::Max),
+            "render" => Ok(Self::Render),
+            _ => Err(format!(
+                "Unknown grid cell size '{}'. Expected one of: max, render.",
+                value
+            )),
+        }
+    }
+}
+
+/// Where frames smaller than their cell are placed in the grid layout.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GridAnchor {
+    TopLeft,
+    Center,
+    /// Centered horizontally, on the bottom of the cell.
+    Bottom,
+}
+
+impl GridAnchor {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "top_left" => Ok(Self::TopLeft),
+            "center" => Ok(Self::Center),
+            "bottom" => Ok(Self::Bottom),
+            _ => Err(format!(
+                "Unknown grid anchor '{}'. Expected one of: top_left, center, bottom.",
+                value
+            )),
+        }
+    }
+}
+
+/// Which frames share crop bounds.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CropPolicy {
+    /// Each frame is cropped on its own.
+    Frame,
+    /// All frames in a direction of an animation.
+    Direction,
+    /// All frames in an animation.
+    Animation,
+    /// All frames in the sheet.
+    Sheet,
+}
+
+impl CropPolicy {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "frame" => Ok(Self::Frame),
+            "direction" => Ok(Self::Direction),
+            "animation" => Ok(Self::Animation),
+            "sheet" => Ok(Self::Sheet),
+            _ => Err(format!(
+                "Unknown crop policy '{}'. Expected one of: frame, direction, animation, sheet.",
+                value
+            )),
+        }
+    }
+}
+
+/// How colors between palette entries are approximated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DitherKind {
+    /// Each pixel uses its nearest palette color.
+    None,
+    /// A Bayer matrix pattern.
+    Ordered,
+    /// Floyd-Steinberg error diffusion.
+    Diffusion,
+}
+
+impl DitherKind {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "ordered" => Ok(Self::Ordered),
+            "diffusion" => Ok(Self::Diffusion),
+            _ => Err(format!(
+                "Unknown dither '{}'. Expected one of: none, ordered, diffusion.",
+                value
+            )),
+        }
+    }
+}
+
+/// How renders made at a larger scale are shrunk back down to the sprite size.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DownsampleFilter {
+    /// Averages each block of pixels.
+    Box,
+    Lanczos,
+    /// Takes the center pixel of each block.
+    Nearest,
+    /// Takes the most common color in each block.
+    Majority,
+}
+
+impl DownsampleFilter {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "box" => Ok(Self::Box),
+            "lanczos" => Ok(Self::Lanczos),
+            "nearest" => Ok(Self::Nearest),
+            "majority" => Ok(Self::Majority),
+            _ => Err(format!(
+                "Unknown downsample filter '{}'. Expected one of: box, lanczos, nearest, majority.",
+                value
+            )),
+        }
+    }
+}
+
+/// Which outlines are drawn around each frame.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutlineKind {
+    None,
+    /// Around the outside of the visible pixels.
+    Outer,
+    /// Over the edge of the visible pixels.
+    Inner,
+    Both,
+}
+
+impl OutlineKind {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "outer" => Ok(Self::Outer),
+            "inner" => Ok(Self::Inner),
+            "both" => Ok(Self::Both),
+            _ => Err(format!(
+                "Unknown outline '{}'. Expected one of: none, outer, inner, both.",
+                value
+            )),
+        }
+    }
+}
+
+/// How outlines wrap around corners.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum OutlineCorners {
+    /// Includes diagonal pixels, for square corners.
+    Square,
+    /// Only includes pixels within the thickness, for rounded corners.
+    Round,
+}
+
+impl OutlineCorners {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "square" => Ok(Self::Square),
+            "round" => Ok(Self::Round),
+            _ => Err(format!(
+                "Unknown outline corners '{}'. Expected one of: square, round.",
+                value
+            )),
+        }
+    }
+}
+
+/// Extra render passes, each packed into its own sheet with the same layout as the color pass.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum PassKind {
+    /// Camera space normals.
+    Normal,
+    /// Distance from the camera, with nearer being brighter.
+    Depth,
+    Emission,
+    /// The pass index of each object.
+    ObjectIndex,
+    /// The pass index of each material.
+    MaterialIndex,
+    /// Marks the pixels that get recolored for each team.
+    TeamMask,
+}
+
+impl PassKind {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "normal" => Ok(Self::Normal),
+            "depth" => Ok(Self::Depth),
+            "emission" => Ok(Self::Emission),
+            "object_index" => Ok(Self::ObjectIndex),
+            "material_index" => Ok(Self::MaterialIndex),
+            "team_mask" => Ok(Self::TeamMask),
+            _ => Err(format!(
+                "Unknown pass '{}'. Expected any of: normal, depth, emission, object_index, material_index, team_mask.",
+                value
+            )),
+        }
+    }
+
+    /// The name of the pass, as used in file names and the sheet metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Depth => "depth",
+            Self::Emission => "emission",
+            Self::ObjectIndex => "object_index",
+            Self::MaterialIndex => "material_index",
+            Self::TeamMask => "team_mask",
+        }
+    }
+}
+
+/// The GPU texture container each page is also written as, alongside its PNG.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TextureFormat {
+    /// Only the PNG.
+    Png,
+    Dds,
+    Ktx2,
+}
+
+impl TextureFormat {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "png" => Ok(Self::Png),
+            "dds" => Ok(Self::Dds),
+            "ktx2" => Ok(Self::Ktx2),
+            _ => Err(format!(
+                "Unknown texture format '{}'. Expected one of: png, dds, ktx2.",
+                value
+            )),
+        }
+    }
+
+    /// The file extension, as used in file names and the sheet metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Dds => "dds",
+            Self::Ktx2 => "ktx2",
+        }
+    }
+}
+
+/// How pages in a texture container are compressed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BlockCompression {
+    /// 4 bits per pixel, with alpha either fully opaque or fully transparent.
+    Bc1,
+    /// 8 bits per pixel, with smooth alpha.
+    Bc3,
+    /// 8 bits per pixel, with the best quality.
+    Bc7,
+}
+
+impl BlockCompression {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "bc1" => Ok(Self::Bc1),
+            "bc3" => Ok(Self::Bc3),
+            "bc7" => Ok(Self::Bc7),
+            _ => Err(format!(
+                "Unknown block compression '{}'. Expected one of: bc1, bc3, bc7.",
+                value
+            )),
+        }
+    }
+
+    /// The name of the compression, as used in the sheet metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bc1 => "bc1",
+            Self::Bc3 => "bc3",
+            Self::Bc7 => "bc7",
+        }
+    }
+
+    /// The size of each compressed 4x4 block of pixels.
+    pub fn block_bytes(&self) -> usize {
+        match self {
+            Self::Bc1 => 8,
+            Self::Bc3 | Self::Bc7 => 16,
+        }
+    }
+}
+
+/// How page images are stored.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ImageOutput {
+    /// As base64 in the sheet JSON.
+    Embedded,
+    /// As files next to the sheet JSON, which refers to them by relative path.
+    File,
+}
+
+impl ImageOutput {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "embedded" => Ok(Self::Embedded),
+            "file" => Ok(Self::File),
+            _ => Err(format!(
+                "Unknown image output '{}'. Expected one of: embedded, file.",
+                value
+            )),
+        }
+    }
+}
+
+/// The formats a sheet's metadata is exported as.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ExporterKind {
+    /// The builder sheet JSON.
+    Builder,
+    /// TexturePacker's JSON, with frames keyed by name.
+    TexturePackerHash,
+    /// TexturePacker's JSON, with frames in a list.
+    TexturePackerArray,
+    /// Aseprite's JSON, with a frame tag for each animation direction.
+    Aseprite,
+    /// A Godot 4 SpriteFrames resource.
+    Godot,
+    /// libGDX's TextureAtlas text format.
+    LibGdx,
+}
+
+impl ExporterKind {
+    pub fn parse<'a>(value: &'a str) -> Result<Self, String> {
+        match value.to_lowercase().as_str() {
+            "builder" => Ok(Self::Builder),
+            "texturepacker_hash" => Ok(Self::TexturePackerHash),
+            "texturepacker_array" => Ok(Self::TexturePackerArray),
+            "aseprite" => Ok(Self::Aseprite),
+            "godot" => Ok(Self::Godot),
+            "libgdx" => Ok(Self::LibGdx),
+            _ => Err(format!(
+                "Unknown exporter '{}'. Expected any of: builder, texturepacker_hash, texturepacker_array, aseprite, godot, libgdx.",
+                value
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set<'a>(name: &'a str, value: &'a str) -> Result<Cfg, String> {
+        let mut cfg = Cfg::new(64, 64, 8);
+        cfg.set_option(name, value)?;
+
+        Ok(cfg)
+    }
+
+    #[test]
+    fn set_option_parses_values() {
+        assert_eq!(
+            PackerKind::Skyline,
+            set("packer", "Skyline").unwrap().packer
+        );
+        assert_eq!(
+            Some(512),
+            set("max_texture_size", "512").unwrap().max_texture_size
+        );
+        assert_eq!(None, set("max_texture_size", "0").unwrap().max_texture_size);
+        assert!(set("allow_rotation", "").unwrap().allow_rotation);
+        assert!(!set("dedupe", "no").unwrap().dedupe);
+        assert_eq!(-20, set("shadow_skew", "-20").unwrap().shadow_skew);
+        assert_eq!(
+            [255, 0, 128, 255],
+            set("outline_color", "#ff0080").unwrap().outline_color
+        );
+        assert_eq!(
+            [0, 0, 0, 64],
+            set("shadow_color", "00000040").unwrap().shadow_color
+        );
+    }
+
+    #[test]
+    fn set_option_rejects_unknown_options() {
+        assert_eq!(Err("Unknown option 'nope'.".to_string()), set("nope", "1"));
+    }
+
+    #[test]
+    fn set_option_rejects_bad_values() {
+        assert_eq!(
+            Err("Option 'padding' expects a positive integer, got '-1'.".to_string()),
+            set("padding", "-1")
+        );
+        assert_eq!(
+            Err(
+                "Option 'alpha_threshold' expects an integer from 0 to 255, got '256'.".to_string()
+            ),
+            set("alpha_threshold", "256")
+        );
+        assert_eq!(
+            Err("Option 'dedupe' expects true or false, got 'maybe'.".to_string()),
+            set("dedupe", "maybe")
+        );
+        assert_eq!(
+            Err("Option 'outline_color' expects a hex color, got 'ff00'.".to_string()),
+            set("outline_color", "ff00")
+        );
+        assert_eq!(
+            Err("Option 'outline_color' expects a hex color, got 'gg0000'.".to_string()),
+            set("outline_color", "gg0000")
+        );
+        assert_eq!(
+            Err("Option 'render_scale' must be at least 1.".to_string()),
+            set("render_scale", "0")
+        );
+        assert_eq!(
+            Err("Option 'palette_colors' expects at most 255 colors, got '256'.".to_string()),
+            set("palette_colors", "256")
+        );
+        assert_eq!(
+            Err("Option 'exporters' needs at least one exporter.".to_string()),
+            set("exporters", " , ")
+        );
+        assert!(set("packer", "tetris")
+            .unwrap_err()
+            .starts_with("Unknown packer 'tetris'."));
+        assert!(set("passes", "normal,nope")
+            .unwrap_err()
+            .starts_with("Unknown pass 'nope'."));
+    }
+
+    #[test]
+    fn list_options_drop_duplicates() {
+        assert_eq!(
+            vec![PassKind::Normal, PassKind::Depth],
+            set("passes", "normal, depth,normal").unwrap().passes
+        );
+        assert_eq!(
+            vec![ExporterKind::Aseprite, ExporterKind::Builder],
+            set("exporters", "aseprite,builder,aseprite")
+                .unwrap()
+                .exporters
+        );
+    }
+
+    #[test]
+    fn normal_map_applies_in_any_order() {
+        let mut cfg = Cfg::new(64, 64, 8);
+        cfg.set_option("normal_map", "true").unwrap();
+        cfg.set_option("passes", "depth").unwrap();
+        assert_eq!(vec![PassKind::Depth, PassKind::Normal], cfg.passes);
+
+        let mut cfg = Cfg::new(64, 64, 8);
+        cfg.set_option("passes", "depth").unwrap();
+        cfg.set_option("normal_map", "true").unwrap();
+        assert_eq!(vec![PassKind::Depth, PassKind::Normal], cfg.passes);
+
+        cfg.set_option("passes", "normal,emission").unwrap();
+        cfg.set_option("normal_map", "false").unwrap();
+        assert_eq!(vec![PassKind::Emission], cfg.passes);
+
+        cfg.set_option("passes", "normal").unwrap();
+        assert!(cfg.passes.is_empty());
+    }
+
+    #[test]
+    fn validate_rejects_rotated_godot_exports() {
+        let mut cfg = Cfg::new(64, 64, 8);
+        cfg.set_option("exporters", "builder,godot").unwrap();
+        assert_eq!(Ok(()), cfg.validate());
+
+        cfg.set_option("allow_rotation", "true").unwrap();
+        assert!(cfg.validate().is_err());
+
+        cfg.set_option("exporters", "builder").unwrap();
+        assert_eq!(Ok(()), cfg.validate());
+    }
+
+    #[test]
+    fn apply_file_sets_options_and_rejects_run_options() {
+        let dir = std::env::temp_dir().join(format!("cfg_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(SHEET_CFG_FILE);
+
+        fs::write(&path, "# A comment\n\npadding = 2\nlayout=grid\n").unwrap();
+        let mut cfg = Cfg::new(64, 64, 8);
+        cfg.apply_file(&path).unwrap();
+        assert_eq!(2, cfg.padding_px);
+        assert_eq!(LayoutKind::Grid, cfg.layout);
+
+        fs::write(&path, "palette=colors.gpl\n").unwrap();
+        assert_eq!(
+            Err("Option 'palette' applies to every sheet, so it can only be set on the command line.".to_string()),
+            cfg.apply_file(&path)
+        );
+
+        fs::write(&path, "allow_rotation=true\nexporters=godot\n").unwrap();
+        assert!(Cfg::new(64, 64, 8).apply_file(&path).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Ok(()), cfg.apply_file(&path));
+    }
+}
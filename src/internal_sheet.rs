@@ -1,56 +1,154 @@
+use crate::cfg::{BlockCompression, TextureFormat};
+use image::RgbaImage;
+
 /// Top level spritesheet for a given sprite.
 #[derive(Clone, Debug, PartialEq)]
 pub struct SpriteSheet {
-    pub image_png_bytes: Vec<u8>,
-    pub width_px: u32,
-    pub height_px: u32,
+    pub pages: Vec<Page>,
+    /// The extra passes, each with a page for every color page in the same layout.
+    pub layers: Vec<Layer>,
+    pub team_mask: Option<TeamMask>,
+    /// The name of the sheet with this sheet's drop shadows.
+    pub shadow_sheet: Option<String>,
+    /// The name of the sheet this sheet is the drop shadows of.
+    pub shadow_of: Option<String>,
     pub animations: Vec<Animation>,
+    pub grid: Option<Grid>,
+    /// Whether the color of each page pixel has been multiplied by its alpha.
+    pub premultiplied_alpha: bool,
+    /// The texture container pages are also written as, if any.
+    pub texture: Option<Texture>,
 }
 
 impl SpriteSheet {
     /// Creates a new sprite sheet.
-    pub fn new(
-        image_png_bytes: Vec<u8>,
-        width_px: u32,
-        height_px: u32,
-        animations: Vec<Animation>,
-    ) -> Self {
+    pub fn new(pages: Vec<Page>, animations: Vec<Animation>) -> Self {
         Self {
-            width_px,
-            height_px,
+            pages,
+            layers: vec![],
+            team_mask: None,
+            shadow_sheet: None,
+            shadow_of: None,
             animations,
-            image_png_bytes,
+            grid: None,
+            premultiplied_alpha: false,
+            texture: None,
         }
     }
 }
 
+/// The uniform cells frames are placed in when using the grid layout.
+/// Each directional animation starts on a new row.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Grid {
+    pub cell_w_px: u32,
+    pub cell_h_px: u32,
+    pub columns: u32,
+    /// The total rows across all pages.
+    pub rows: u32,
+}
+
+/// The cell a frame was placed in when using the grid layout.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GridCell {
+    pub column: u32,
+    pub row: u32,
+}
+
+/// A single image in the sprite sheet.
+/// The PNG bytes are empty until the page has been encoded, and the texture bytes unless there's a texture container.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Page {
+    pub image: RgbaImage,
+    pub image_png_bytes: Vec<u8>,
+    pub texture_bytes: Vec<u8>,
+    pub width_px: u32,
+    pub height_px: u32,
+}
+
+/// The block compressed texture container each page is also written as.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Texture {
+    pub format: TextureFormat,
+    pub compression: BlockCompression,
+    pub mipmaps: bool,
+}
+
+/// An extra render pass, such as normals or depth, laid out the same as the color pages.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub name: String,
+    pub pages: Vec<Page>,
+}
+
+/// Which layer marks the pixels that get recolored for each team.
+/// The mask is in the red channel of the layer, with 255 being fully recolorable.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TeamMask {
+    pub layer: String,
+    pub source: TeamMaskSource,
+    /// A lookup texture with a row for each team color, shaded by the brightness of the masked pixel.
+    pub lut: Option<Page>,
+    pub team_colors: Vec<[u8; 3]>,
+}
+
+/// Where the team color mask came from.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum TeamMaskSource {
+    /// Rendered by Blender, from materials writing to the mask.
+    Pass,
+    /// Generated from the color pass, from pixels close to the key color.
+    ColorKey,
+}
+
 /// An animation in the sprite sheet
-/// Positions are relative to the parent object.
+/// Positions are the top left most point of its directional animations on the same page.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Animation {
     pub directional_animations: Vec<DirectionalAnimation>,
     pub name: String,
+    /// The frames per second of the Blender scene.
+    pub fps: f32,
+    /// How many Blender frames apart each rendered frame is.
+    pub frame_step: u32,
+    pub looping: bool,
+    pub page: u32,
     pub start_x_px: u32,
     pub start_y_px: u32,
 }
 
 /// A directional animation in the sprite sheet.
-/// Positions are relative to the parent object.
+/// Positions are the top left most point of its frames on the same page.
 #[derive(Clone, Debug, PartialEq)]
 pub struct DirectionalAnimation {
     pub direction: u32,
     pub frames: Vec<Frame>,
+    pub page: u32,
     pub start_x_px: u32,
     pub start_y_px: u32,
 }
 
 /// An individual frame in the animation.
-/// Positions are relative to the parent object.
+/// Start positions are where the frame was packed on its page.
+/// The source size is the full, uncropped render. Offsets, along with the width and height, are the
+/// trimmed rect the frame was cropped to, in source pixels from the top left of the render.
+/// The pivot is also in source pixels, and may be outside of the trimmed rect.
+/// Rotated frames are stored rotated 90 degrees clockwise, taking up `height_px` x `width_px` on the page.
+/// Empty frames had nothing visible, so they keep their place in the animation without any pixels.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Frame {
+    /// How long the frame is shown for, until the next rendered frame.
+    pub duration_ms: u32,
+    pub empty: bool,
+    pub grid_cell: Option<GridCell>,
     pub height_px: u32,
     pub offset_x_px: u32,
     pub offset_y_px: u32,
+    pub page: u32,
+    pub pivot_px: Option<(f32, f32)>,
+    pub rotated: bool,
+    pub source_w_px: u32,
+    pub source_h_px: u32,
     pub start_x_px: u32,
     pub start_y_px: u32,
     pub width_px: u32,
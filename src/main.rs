@@ -1,3 +1,4 @@
+mod cfg;
 mod internal_sheet;
 mod render;
 mod render_blend_file;
@@ -6,14 +7,17 @@ mod render_model_sheet;
 use benchy::Benchy;
 use image::DynamicImage;
 
-pub type Render = (internal_sheet::Animation, DynamicImage);
+pub use cfg::Cfg;
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub struct Cfg {
-    pub output_render_pngs: bool,
-    pub sprite_w_px: u32,
-    pub sprite_h_px: u32,
-    pub num_directions: u32,
+/// A rendered animation, with the cropped images for each frame of each direction.
+pub type Render = (internal_sheet::Animation, Vec<Vec<FrameImgs>>);
+
+/// The cropped images for a single frame.
+/// Every pass is cropped to the same bounds as the color pass, so they line up pixel for pixel.
+pub struct FrameImgs {
+    pub color: DynamicImage,
+    /// The extra passes Blender rendered for the frame.
+    pub passes: Vec<(cfg::PassKind, DynamicImage)>,
 }
 
 fn main() {
@@ -52,12 +56,27 @@ fn main() {
     };
 
     // Build cfg
-    let cfg = Cfg {
-        output_render_pngs,
-        sprite_w_px,
-        sprite_h_px,
-        num_directions,
-    };
+    let mut cfg = Cfg::new(sprite_w_px, sprite_h_px, num_directions);
+    cfg.output_render_pngs = output_render_pngs;
+
+    // Apply any '--name=value' options
+    for arg in std::env::args().skip(6) {
+        if let Some(option) = arg.strip_prefix("--") {
+            let mut split = option.splitn(2, '=');
+            let name = split.next().unwrap_or_default();
+            let value = split.next().unwrap_or_default();
+
+            if let Err(e) = cfg.set_option(name, value) {
+                println!("{}", e);
+                return;
+            }
+        }
+    }
+
+    if let Err(e) = cfg.validate() {
+        println!("{}", e);
+        return;
+    }
 
     // Clean output folder
     match std::fs::remove_dir_all(&output_path) {
@@ -79,7 +98,9 @@ fn main() {
 fn print_help() {
     println!("Welcome to the sprite_maker!");
     println!("Command line format:");
-    println!("\tinput_path output_path sprite_width sprite_height num_directions -o?");
+    println!(
+        "\tinput_path output_path sprite_width sprite_height num_directions -o? --option=value*"
+    );
     println!();
     println!("Argument Descriptions");
     println!("\tinput_path");
@@ -97,4 +118,137 @@ fn print_help() {
     println!("\t-o");
     println!("\t\t-Outputs a PNG of the sprite sheet for debugging.");
     println!();
+    println!("Options");
+    println!("\t--packer=maxrects");
+    println!("\t\t-The algorithm used to lay out frames in the sheet.");
+    println!("\t\t-One of: maxrects (default), skyline, guillotine, shelf, grid.");
+    println!("\t--max_texture_size=0");
+    println!("\t\t-The largest width or height of a sheet image, in pixels. 0 means no limit.");
+    println!("\t\t-Frames that don't fit get split across additional pages.");
+    println!("\t--dedupe=true");
+    println!("\t\t-Pixel-identical frames share a single region of the sheet.");
+    println!("\t--padding=0");
+    println!("\t\t-Transparent pixels between packed frames.");
+    println!("\t--extrude=0");
+    println!("\t\t-Pixels to repeat the edges of each frame outward by, to prevent bleeding when filtering.");
+    println!("\t--border=0");
+    println!("\t\t-Transparent pixels around the edge of the sheet.");
+    println!("\t--allow_rotation=false");
+    println!("\t\t-Lets the packer rotate frames 90 degrees clockwise to save space.");
+    println!(
+        "\t\t-Rotated frames are flagged in the output. Ignored by the grid and shelf packers."
+    );
+    println!("\t--layout=packed");
+    println!("\t\t-One of: packed (default), grid.");
+    println!("\t\t-Grid gives every frame an identical cell, with a row for each direction, and ignores --packer.");
+    println!("\t--grid_cell=max");
+    println!("\t\t-The grid cell size. One of: max (largest cropped frame, default), render (sprite size).");
+    println!("\t\t-Render cells keep each frame where it was in the render.");
+    println!("\t--grid_anchor=bottom");
+    println!(
+        "\t\t-Where frames sit in max sized cells. One of: top_left, center, bottom (default)."
+    );
+    println!("\t--grid_columns=0");
+    println!("\t\t-Wraps rows after this many frames. 0 means one row per direction.");
+    println!("\t--pivot_object=pivot");
+    println!("\t\t-The Blender object projected into each frame as its pivot.");
+    println!("\t\t-If the scene has no object by this name, the world origin is used.");
+    println!("\t--crop=frame");
+    println!("\t\t-Which frames share the same crop bounds, so they stay the same size.");
+    println!("\t\t-One of: frame (default), direction, animation, sheet.");
+    println!("\t--alpha_threshold=0");
+    println!(
+        "\t\t-Pixels with an alpha at or below this are ignored when cropping, from 0 to 255."
+    );
+    println!("\t\t-Frames with nothing above it are kept as empty frames.");
+    println!("\t--alpha_bleed=true");
+    println!("\t\t-Fills the color of transparent pixels from their neighbours, to prevent dark fringes when filtering.");
+    println!("\t--premultiply_alpha=false");
+    println!("\t\t-Multiplies the color of each pixel by its alpha. Flagged in the JSON.");
+    println!("\t--palette=");
+    println!("\t\t-A .gpl, .hex or .png palette file every sheet is restricted to, written as indexed PNGs.");
+    println!("\t--palette_colors=0");
+    println!("\t\t-Generates a palette of up to this many colors, from 1 to 255, shared by every sheet in the run.");
+    println!("\t\t-0 means no palette. Ignored when --palette is given.");
+    println!("\t\t-Palette options can only be set on the command line.");
+    println!("\t--dither=none");
+    println!(
+        "\t\t-How colors are matched to the palette. One of: none (default), ordered, diffusion."
+    );
+    println!("\t--render_scale=1");
+    println!("\t\t-Renders at this many times the sprite size, then downsamples each frame before cropping.");
+    println!("\t--downsample=box");
+    println!(
+        "\t\t-The filter used to downsample. One of: box (default), lanczos, nearest, majority."
+    );
+    println!("\t\t-Box and lanczos give smooth supersampled edges, while nearest and majority keep pixel art crisp.");
+    println!("\t--outline=none");
+    println!(
+        "\t\t-Draws an outline around each frame. One of: none (default), outer, inner, both."
+    );
+    println!("\t--outline_color=000000");
+    println!("\t\t-The outline color, as rrggbb or rrggbbaa hex.");
+    println!("\t--outline_thickness=1");
+    println!("\t\t-The outline thickness, in pixels.");
+    println!("\t--outline_corners=square");
+    println!("\t\t-One of: square (default), round.");
+    println!("\t--passes=");
+    println!("\t\t-Comma separated extra passes to render for every frame, each packed into its own sheet with the same layout.");
+    println!("\t\t-Any of: normal, depth, emission, object_index, material_index, team_mask. Index passes need Cycles.");
+    println!("\t\t-Normals are in camera space, with +x right, +y up and +z towards the camera.");
+    println!("\t\t-Team masks are read from a 'team_mask' shader AOV that materials write to.");
+    println!("\t--normal_map=false");
+    println!("\t\t-Shorthand for adding or removing the normal pass, whether it's given before or after --passes.");
+    println!("\t--team_color_key=");
+    println!(
+        "\t\t-An rrggbb color whose hue marks team colored pixels, when there's no team_mask pass."
+    );
+    println!("\t--team_color_tolerance=20");
+    println!("\t\t-How many degrees of hue a pixel may be from the key and still be team colored.");
+    println!("\t--team_colors=");
+    println!("\t\t-Comma separated rrggbb team colors, written to a palette-swap lookup texture.");
+    println!("\t\t-Each row of the lookup is a team, and each column maps mask brightness to its shaded color.");
+    println!("\t--shadow=false");
+    println!("\t\t-Makes a drop shadow sheet, named after the model with a _shadow suffix, by projecting each frame's silhouette onto the ground and blurring it.");
+    println!("\t\t-Shadow frames have the same render size and pivot as their frame, so both are drawn at the same position.");
+    println!("\t--shadow_color=00000080");
+    println!("\t\t-The shadow color, as rrggbb or rrggbbaa hex.");
+    println!("\t--shadow_length=50");
+    println!("\t\t-The shadow's height as a percent of the sprite's, measured from the pivot.");
+    println!("\t--shadow_skew=0");
+    println!(
+        "\t\t-How far the shadow leans sideways, as a percent of its height. Negative leans left."
+    );
+    println!("\t--shadow_blur=2");
+    println!("\t\t-The shadow blur radius, in pixels.");
+    println!("\t--texture_format=png");
+    println!("\t\t-Also writes each page as a GPU texture container, embedded in the JSON. One of: png (default, none), dds, ktx2.");
+    println!("\t\t-Pages are padded to a multiple of 4 pixels. Colors are sRGB, while extra passes are linear.");
+    println!("\t--block_compression=bc3");
+    println!("\t\t-How texture containers are compressed. One of: bc1, bc3 (default), bc7.");
+    println!("\t\t-BC1 has on or off alpha, BC3 smooth alpha, and BC7 the best quality.");
+    println!("\t--mipmaps=false");
+    println!("\t\t-Includes a full mip chain in texture containers.");
+    println!("\t--image_output=embedded");
+    println!(
+        "\t\t-How page images are stored. One of: embedded (default, base64 in the JSON), file."
+    );
+    println!(
+        "\t\t-Files are written next to the JSON, which has their relative path and CRC32 instead."
+    );
+    println!("\t--exporters=builder");
+    println!("\t\t-Comma separated formats to export each sheet as. Any of: builder (default), texturepacker_hash, texturepacker_array, aseprite, godot, libgdx.");
+    println!(
+        "\t\t-Other formats are written next to the builder JSON, with the pages as PNG files."
+    );
+    println!("\t\t-TexturePacker JSON has one file per page. Aseprite JSON has a frame tag for each direction, named like walk_1.");
+    println!("\t\t-Godot gets a SpriteFrames .tres named after the model, with an animation like walk_1 for each direction. It can't be used with allow_rotation.");
+    println!("\t\t-libGDX gets a .atlas named after the model, with a region like walk_1 for each frame and its index in the animation.");
+    println!();
+    println!(
+        "Any option may also be set for a single sheet by adding a '{}' file to its folder,",
+        cfg::SHEET_CFG_FILE
+    );
+    println!("with one 'option=value' per line.");
+    println!();
 }
@@ -1,6 +1,8 @@
 use std::fs::{self, DirEntry};
 
-use crate::{render_blend_file, render_model_sheet, Cfg};
+use crate::{
+    cfg::SHEET_CFG_FILE, render_blend_file, render_model_sheet, render_model_sheet::Sheet, Cfg,
+};
 
 /// Renders a single model's sheets
 pub fn execute<'a>(input_path: &'a str, output_path: &'a str, cfg: &Cfg) {
@@ -15,14 +17,41 @@ pub fn execute<'a>(input_path: &'a str, output_path: &'a str, cfg: &Cfg) {
         panic!("No nested folders found in input directory! Please ensure all .blend files are under subdirectories in the input directory.");
     }
 
-    // Render sprites for each folder
+    // A generated palette covers every sheet in the run, so they're saved once they're all made
+    let generates_palette = cfg.palette.is_none() && cfg.palette_colors > 0;
+    if generates_palette {
+        let sheets: Vec<Sheet> = folders
+            .into_iter()
+            .flat_map(|folder| render_folder(folder, cfg.clone()))
+            .collect();
+
+        let palette = render_model_sheet::palette(cfg, &sheets);
+
+        for sheet in sheets {
+            render_model_sheet::save(output_path, sheet, palette.as_ref());
+        }
+
+        return;
+    }
+
+    // Otherwise each sheet is saved as soon as it's made
+    let palette = render_model_sheet::palette(cfg, &[]);
+
     for folder in folders {
-        render_folder(folder, output_path, cfg.clone())
+        for sheet in render_folder(folder, cfg.clone()) {
+            render_model_sheet::save(output_path, sheet, palette.as_ref());
+        }
     }
 }
 
 /// Renders the folder
-fn render_folder<'a>(folder: DirEntry, output_path: &'a str, cfg: Cfg) {
+fn render_folder(folder: DirEntry, mut cfg: Cfg) -> Vec<Sheet> {
+    // Apply any sheet specific options
+    let sheet_cfg_path = folder.path().join(SHEET_CFG_FILE);
+    if let Err(e) = cfg.apply_file(&sheet_cfg_path) {
+        panic!("Error reading sheet config {:?}: {}", sheet_cfg_path, e);
+    }
+
     // Make temp directory for renders
     let temp_path = {
         let mut temp_path = folder.path().clone();
@@ -52,13 +81,9 @@ fn render_folder<'a>(folder: DirEntry, output_path: &'a str, cfg: Cfg) {
         render_blend_file::execute(blend_file, &model_name, &temp_path, cfg.clone());
     }
 
-    // Make the sheet
-    render_model_sheet::execute(
-        &temp_path.to_str().unwrap_or_default(),
-        output_path,
-        &model_name,
-        cfg,
-    );
+    // Make the sheets
+    let sheets =
+        render_model_sheet::make(&temp_path.to_str().unwrap_or_default(), &model_name, cfg);
 
     // Clean temp folder
     match std::fs::remove_dir_all(&temp_path) {
@@ -68,4 +93,6 @@ fn render_folder<'a>(folder: DirEntry, output_path: &'a str, cfg: Cfg) {
             temp_path, e
         ),
     }
+
+    sheets
 }
@@ -14,8 +14,10 @@ pub fn execute<'a>(blend_file: DirEntry, model_name: &'a str, temp_path: &PathBu
 
     let path = blend_file.path();
     let file_name = path.to_str().unwrap_or_default();
-    let render_width = cfg.sprite_w_px;
-    let render_height = cfg.sprite_h_px;
+
+    // Renders get downsampled to the sprite size once they're made
+    let render_width = cfg.sprite_w_px * cfg.render_scale;
+    let render_height = cfg.sprite_h_px * cfg.render_scale;
 
     // Write script to temp dir
     let mut script_path = temp_path.clone();
@@ -36,6 +38,14 @@ pub fn execute<'a>(blend_file: DirEntry, model_name: &'a str, temp_path: &PathBu
         .arg(render_width.to_string())
         .arg(render_height.to_string())
         .arg(cfg.num_directions.to_string())
+        .arg(&cfg.pivot_object)
+        .arg(
+            cfg.passes
+                .iter()
+                .map(|p| p.name())
+                .collect::<Vec<_>>()
+                .join(","),
+        )
         // Execute
         .output();
 
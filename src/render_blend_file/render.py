@@ -7,6 +7,7 @@ import bpy
 import math
 import os
 import sys
+from bpy_extras.object_utils import world_to_camera_view
 from math import cos, sin, radians
 from mathutils import Vector, Euler
 
@@ -27,6 +28,10 @@ MODEL_NAME = argv[1]
 RENDER_WIDTH = int(argv[2])
 RENDER_HEIGHT = int(argv[3])
 NUM_ROTATIONS = int(argv[4])
+PIVOT_NAME = argv[5]
+PASSES = [p for p in argv[6].split(',') if p]
+
+PASS_NODE_PREFIX = "__renderer_pass_"
 
 
 # Set the scene coordinates
@@ -95,13 +100,237 @@ def set_lighting(rotation):
     dg.update()
 
 
+def new_pass_node(tree, node_type, name):
+    node = tree.nodes.new(node_type)
+    node.name = PASS_NODE_PREFIX + name
+    return node
+
+
+# Combines the values into a single color, using the render's alpha.
+def combine_color(tree, layers, name, r, g, b):
+    try:
+        combine = new_pass_node(tree, 'CompositorNodeCombineColor', name)
+    except RuntimeError:
+        # Older versions of Blender
+        combine = new_pass_node(tree, 'CompositorNodeCombRGBA', name)
+
+    for idx, value in enumerate([r, g, b]):
+        tree.links.new(value, combine.inputs[idx])
+    tree.links.new(layers.outputs['Alpha'], combine.inputs[3])
+
+    return combine.outputs[0]
+
+
+# Maps the value from [from_min, from_max] to [to_min, to_max].
+def map_range(tree, name, value, from_min, from_max, to_min, to_max):
+    node = new_pass_node(tree, 'CompositorNodeMapRange', name)
+    node.use_clamp = True
+    node.inputs[1].default_value = from_min
+    node.inputs[2].default_value = from_max
+    node.inputs[3].default_value = to_min
+    node.inputs[4].default_value = to_max
+    tree.links.new(value, node.inputs[0])
+
+    return node.outputs[0]
+
+
+# Each channel is the dot product of the world space normal with an axis of the camera, mapped from [-1, 1] to [0, 1].
+def normal_pass(tree, layers):
+    bpy.context.view_layer.use_pass_normal = True
+
+    channels = []
+    for axis in ['x', 'y', 'z']:
+        dot = new_pass_node(tree, 'CompositorNodeNormal', f'normal_{axis}')
+        tree.links.new(layers.outputs['Normal'], dot.inputs[0])
+        channels.append(map_range(tree, f'normal_{axis}_to_color', dot.outputs['Dot'], -1.0, 1.0, 0.0, 1.0))
+
+    return combine_color(tree, layers, 'normal_combine', *channels)
+
+
+# The range gets set for each perspective, since it depends on where the camera is.
+def depth_pass(tree, layers):
+    bpy.context.view_layer.use_pass_z = True
+
+    depth = layers.outputs['Depth'] if 'Depth' in layers.outputs else layers.outputs['Z']
+    value = map_range(tree, 'depth_range', depth, 0.0, 1.0, 1.0, 0.0)
+
+    return combine_color(tree, layers, 'depth_combine', value, value, value)
+
+
+def emission_pass(tree, layers):
+    bpy.context.view_layer.use_pass_emit = True
+
+    set_alpha = new_pass_node(tree, 'CompositorNodeSetAlpha', 'emission_alpha')
+    tree.links.new(layers.outputs['Emit'], set_alpha.inputs['Image'])
+    tree.links.new(layers.outputs['Alpha'], set_alpha.inputs['Alpha'])
+
+    return set_alpha.outputs[0]
+
+
+# Each index is written as its value out of 255, so the index can be read back from any channel of the 8 bit PNG.
+# Index passes are only supported by Cycles.
+def index_pass(tree, layers, name, output_name):
+    to_color = new_pass_node(tree, 'CompositorNodeMath', f'{name}_to_color')
+    to_color.operation = 'DIVIDE'
+    to_color.inputs[1].default_value = 255.0
+    tree.links.new(layers.outputs[output_name], to_color.inputs[0])
+
+    value = to_color.outputs[0]
+    return combine_color(tree, layers, f'{name}_combine', value, value, value)
+
+
+def object_index_pass(tree, layers):
+    bpy.context.view_layer.use_pass_object_index = True
+    return index_pass(tree, layers, 'object_index', 'IndexOB')
+
+
+def material_index_pass(tree, layers):
+    bpy.context.view_layer.use_pass_material_index = True
+    return index_pass(tree, layers, 'material_index', 'IndexMA')
+
+
+# Materials mark recolorable surfaces by writing 1 to a 'team_mask' AOV Output node,
+# e.g. from a vertex group through an Attribute node.
+def team_mask_pass(tree, layers):
+    view_layer = bpy.context.view_layer
+    if 'team_mask' not in view_layer.aovs:
+        aov = view_layer.aovs.add()
+        aov.name = 'team_mask'
+        aov.type = 'VALUE'
+        view_layer.update()
+
+    value = layers.outputs['team_mask']
+    return combine_color(tree, layers, 'team_mask_combine', value, value, value)
+
+
+PASS_SETUPS = {
+    'normal': normal_pass,
+    'depth': depth_pass,
+    'emission': emission_pass,
+    'object_index': object_index_pass,
+    'material_index': material_index_pass,
+    'team_mask': team_mask_pass,
+}
+
+
+# Sets up the compositor to write each extra pass next to the frames, with the render's alpha.
+def setup_passes():
+    scene = bpy.context.scene
+    scene.use_nodes = True
+    tree = scene.node_tree
+
+    # remove any existing nodes in the event we're using a previously rendered scene
+    for node in [n for n in tree.nodes if n.name.startswith(PASS_NODE_PREFIX)]:
+        tree.nodes.remove(node)
+
+    # Keep the regular render going to the composite output
+    layers = next((n for n in tree.nodes if n.type == 'R_LAYERS'), None)
+    if layers is None:
+        layers = new_pass_node(tree, 'CompositorNodeRLayers', 'layers')
+    if next((n for n in tree.nodes if n.type == 'COMPOSITE'), None) is None:
+        composite = new_pass_node(tree, 'CompositorNodeComposite', 'composite')
+        tree.links.new(layers.outputs['Image'], composite.inputs['Image'])
+
+    for name in PASSES:
+        image = PASS_SETUPS[name](tree, layers)
+
+        output = new_pass_node(tree, 'CompositorNodeOutputFile', f'{name}_output')
+        output.format.file_format = 'PNG'
+        output.format.color_mode = 'RGBA'
+        output.format.color_depth = '8'
+
+        # Passes are data, so don't let the view transform change them
+        if hasattr(output.format, 'color_management'):
+            output.format.color_management = 'OVERRIDE'
+            output.format.view_settings.view_transform = 'Raw'
+
+        tree.links.new(image, output.inputs[0])
+
+
+# Points the passes at the camera's current position, and names their files after the frames.
+def update_passes(perspective):
+    scene = bpy.context.scene
+    tree = scene.node_tree
+
+    bpy.context.view_layer.update()
+    camera = scene.camera.matrix_world
+
+    if 'normal' in PASSES:
+        # The camera looks down -z, so its axes are right, up and towards the camera
+        rotation = camera.to_3x3()
+        for idx, axis in enumerate(['x', 'y', 'z']):
+            camera_axis = rotation.col[idx].normalized()
+
+            # The compositor negates the dot product
+            tree.nodes[PASS_NODE_PREFIX + f'normal_{axis}'].outputs[0].default_value = -camera_axis
+
+    if 'depth' in PASSES:
+        # Cover the orthographic view's worth of depth around the origin
+        distance = camera.translation.length
+        depth_range = tree.nodes[PASS_NODE_PREFIX + 'depth_range']
+        depth_range.inputs[1].default_value = distance - ORTHO_SCALE
+        depth_range.inputs[2].default_value = distance + ORTHO_SCALE
+
+    # Blender fills in the frame number for the '#'s.
+    for name in PASSES:
+        output = tree.nodes[PASS_NODE_PREFIX + f'{name}_output']
+        output.base_path = f'{CWD}/{OUTPUT_PATH}'
+        output.file_slots[0].path = f'{FILE_NAME}_ESCAPED{perspective}_ESCAPED####_ESCAPED{name}'
+
+
 # Triggers a render
 def render(perspective):
+    if PASSES:
+        update_passes(perspective)
+
     # Trigger render
     # Use '_ESCAPED' to prevent blender file names from mucking with the Rust parsing.
     bpy.context.scene.render.filepath = f'{CWD}/{OUTPUT_PATH}/{FILE_NAME}_ESCAPED{perspective}_ESCAPED'
     bpy.ops.render.render(animation=True, write_still=True)
 
+    write_pivots(perspective)
+
+
+# Writes the pivot for each frame, as pixels from the top left of the render.
+# The pivot is the object named PIVOT_NAME if there is one, otherwise the world origin.
+def write_pivots(perspective):
+    scene = bpy.context.scene
+    camera = scene.camera
+    pivot = scene.objects.get(PIVOT_NAME)
+    initial_frame = scene.frame_current
+
+    if camera is None:
+        return
+
+    for frame in range(scene.frame_start, scene.frame_end + 1, scene.frame_step):
+        scene.frame_set(frame)
+
+        location = pivot.matrix_world.translation if pivot else Vector((0, 0, 0))
+
+        # Camera view coordinates are normalized from the bottom left
+        view = world_to_camera_view(scene, camera, location)
+        x = view.x * RENDER_WIDTH
+        y = (1.0 - view.y) * RENDER_HEIGHT
+
+        # Named the same as the frame's render, so the Rust side can pair them up.
+        path = f'{CWD}/{OUTPUT_PATH}/{FILE_NAME}_ESCAPED{perspective}_ESCAPED{frame:04d}.pivot'
+        with open(path, 'w') as file:
+            file.write(f'{x} {y}')
+
+    scene.frame_set(initial_frame)
+
+
+# Writes the animation's playback settings, so runtimes play it at the same speed.
+# Animations loop unless the scene has a 'loop' custom property set to false.
+def write_playback():
+    scene = bpy.context.scene
+    fps = scene.render.fps / scene.render.fps_base
+    loop = 'true' if scene.get('loop', True) else 'false'
+
+    path = f'{CWD}/{OUTPUT_PATH}/{FILE_NAME}_ESCAPED.anim'
+    with open(path, 'w') as file:
+        file.write(f'fps={fps}\nframe_step={scene.frame_step}\nloop={loop}\n')
+
 
 def render_isometric():
     degs_per_rotation = 360.0 / float(NUM_ROTATIONS)
@@ -134,4 +363,8 @@ def render_isometric():
         render(rotation_idx + 1)
 
 
+if PASSES:
+    setup_passes()
+
+write_playback()
 render_isometric()
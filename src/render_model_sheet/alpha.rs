@@ -0,0 +1,99 @@
+use benchy::Benchy;
+use image::RgbaImage;
+
+/// Fills the color of fully transparent pixels from their nearest visible neighbours, leaving alpha untouched.
+/// This keeps filtering and mipmapping from pulling in Blender's black background at the edges of frames.
+pub fn bleed(img: &mut RgbaImage) {
+    Benchy::time("alpha_bleed");
+
+    let (w, h) = img.dimensions();
+    let idx = |x: u32, y: u32| (y * w + x) as usize;
+
+    // Visible pixels are where colors bleed from
+    let mut filled: Vec<bool> = img.pixels().map(|p| p[3] != 0).collect();
+
+    let neighbours = |x: u32, y: u32| {
+        let mut n = Vec::with_capacity(8);
+        for dy in -1i64..=1 {
+            for dx in -1i64..=1 {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                let is_self = dx == 0 && dy == 0;
+                if !is_self && nx >= 0 && ny >= 0 && nx < w as i64 && ny < h as i64 {
+                    n.push((nx as u32, ny as u32));
+                }
+            }
+        }
+
+        n
+    };
+
+    // Start with the transparent pixels touching visible ones
+    let mut queued = filled.clone();
+    let mut layer = vec![];
+    for y in 0..h {
+        for x in 0..w {
+            if !filled[idx(x, y)]
+                && neighbours(x, y)
+                    .iter()
+                    .any(|(nx, ny)| filled[idx(*nx, *ny)])
+            {
+                queued[idx(x, y)] = true;
+                layer.push((x, y));
+            }
+        }
+    }
+
+    // Grow outward a ring at a time, so each pixel takes the average of the closest colors
+    while !layer.is_empty() {
+        let mut colors = Vec::with_capacity(layer.len());
+        for (x, y) in layer.iter() {
+            let mut sum = [0u32; 3];
+            let mut count = 0;
+            for (nx, ny) in neighbours(*x, *y) {
+                if filled[idx(nx, ny)] {
+                    let p = img.get_pixel(nx, ny);
+                    for c in 0..3 {
+                        sum[c] += p[c] as u32;
+                    }
+                    count += 1;
+                }
+            }
+
+            colors.push([
+                (sum[0] / count) as u8,
+                (sum[1] / count) as u8,
+                (sum[2] / count) as u8,
+            ]);
+        }
+
+        let mut next_layer = vec![];
+        for ((x, y), color) in layer.iter().zip(colors) {
+            let p = img.get_pixel_mut(*x, *y);
+            p[0] = color[0];
+            p[1] = color[1];
+            p[2] = color[2];
+            filled[idx(*x, *y)] = true;
+
+            for (nx, ny) in neighbours(*x, *y) {
+                if !queued[idx(nx, ny)] {
+                    queued[idx(nx, ny)] = true;
+                    next_layer.push((nx, ny));
+                }
+            }
+        }
+
+        layer = next_layer;
+    }
+}
+
+/// Multiplies the color of each pixel by its alpha.
+pub fn premultiply(img: &mut RgbaImage) {
+    Benchy::time("premultiply_alpha");
+
+    for p in img.pixels_mut() {
+        let a = p[3] as u32;
+        for c in 0..3 {
+            p[c] = ((p[c] as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
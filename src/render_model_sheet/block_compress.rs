@@ -0,0 +1,555 @@
+use crate::cfg::BlockCompression;
+use benchy::Benchy;
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+
+/// Pixels with an alpha below this are transparent in BC1, which only has a single bit of alpha.
+const BC1_OPAQUE_ALPHA: u8 = 128;
+
+/// The interpolation weights, out of 64, for BC7's 4 bit indexes.
+const BC7_WEIGHTS: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+/// Returns the image followed by each smaller mip level, halving down to 1x1.
+pub fn mip_chain(img: &RgbaImage) -> Vec<RgbaImage> {
+    let mut levels = vec![img.clone()];
+
+    loop {
+        let last = levels.last().unwrap();
+        let (w, h) = last.dimensions();
+        if w <= 1 && h <= 1 {
+            break;
+        }
+
+        let next = imageops::resize(last, (w / 2).max(1), (h / 2).max(1), FilterType::Triangle);
+        levels.push(next);
+    }
+
+    levels
+}
+
+/// Compresses the image a 4x4 block at a time, in row order.
+/// Blocks past the edge of images that aren't a multiple of 4, such as small mip levels, repeat the last row and column.
+pub fn execute(img: &RgbaImage, compression: BlockCompression) -> Vec<u8> {
+    Benchy::time("block_compress");
+
+    let (w, h) = img.dimensions();
+    let (blocks_x, blocks_y) = (w.div_ceil(4), h.div_ceil(4));
+    let mut bytes = Vec::with_capacity((blocks_x * blocks_y) as usize * compression.block_bytes());
+
+    for block_y in 0..blocks_y {
+        for block_x in 0..blocks_x {
+            let mut block = [[0u8; 4]; 16];
+            for (i, p) in block.iter_mut().enumerate() {
+                let x = (block_x * 4 + i as u32 % 4).min(w - 1);
+                let y = (block_y * 4 + i as u32 / 4).min(h - 1);
+                *p = img.get_pixel(x, y).0;
+            }
+
+            match compression {
+                BlockCompression::Bc1 => bytes.extend_from_slice(&bc1(&block, true)),
+                BlockCompression::Bc3 => {
+                    bytes.extend_from_slice(&bc3_alpha(&block));
+                    bytes.extend_from_slice(&bc1(&block, false));
+                }
+                BlockCompression::Bc7 => bytes.extend_from_slice(&bc7(&block)),
+            }
+        }
+    }
+
+    bytes
+}
+
+/// Compresses the color of a block to BC1.
+/// With punch through alpha, transparent pixels use BC1's transparent index. BC3 stores alpha on its own, so it doesn't.
+fn bc1(block: &[[u8; 4]; 16], punch_through: bool) -> [u8; 8] {
+    let is_transparent = |p: &[u8; 4]| punch_through && p[3] < BC1_OPAQUE_ALPHA;
+
+    let colors: Vec<[f32; 4]> = block
+        .iter()
+        .filter(|p| !is_transparent(p))
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32, 0.0])
+        .collect();
+
+    // The 3 color mode, with every pixel transparent
+    if colors.is_empty() {
+        return [0, 0, 0, 0, 255, 255, 255, 255];
+    }
+
+    let (start, end) = endpoints(&colors);
+    let (mut color0, mut color1) = (to_565(end), to_565(start));
+
+    // The order of the endpoints picks the mode. 4 colors needs color0 > color1, and 3 colors plus transparent the opposite.
+    let has_transparent = colors.len() < block.len();
+    if (has_transparent && color0 > color1) || (!has_transparent && color0 < color1) {
+        std::mem::swap(&mut color0, &mut color1);
+    }
+
+    let (c0, c1) = (from_565(color0), from_565(color1));
+    let palette = if color0 > color1 {
+        vec![c0, c1, lerp(c0, c1, 1.0 / 3.0), lerp(c0, c1, 2.0 / 3.0)]
+    } else {
+        vec![c0, c1, lerp(c0, c1, 0.5)]
+    };
+
+    let mut indexes = 0u32;
+    for (i, p) in block.iter().enumerate() {
+        let index = if is_transparent(p) {
+            3
+        } else {
+            nearest(&palette, [p[0] as f32, p[1] as f32, p[2] as f32, 0.0])
+        };
+
+        indexes |= index << (i * 2);
+    }
+
+    let mut bytes = [0u8; 8];
+    bytes[0..2].copy_from_slice(&color0.to_le_bytes());
+    bytes[2..4].copy_from_slice(&color1.to_le_bytes());
+    bytes[4..8].copy_from_slice(&indexes.to_le_bytes());
+
+    bytes
+}
+
+/// Compresses the alpha of a block, as the first half of a BC3 block.
+fn bc3_alpha(block: &[[u8; 4]; 16]) -> [u8; 8] {
+    let alpha0 = block.iter().map(|p| p[3]).max().unwrap_or_default();
+    let alpha1 = block.iter().map(|p| p[3]).min().unwrap_or_default();
+
+    // With alpha0 > alpha1 there are 6 alphas evenly spaced between them
+    let mut palette = vec![
+        [alpha0 as f32, 0.0, 0.0, 0.0],
+        [alpha1 as f32, 0.0, 0.0, 0.0],
+    ];
+    if alpha0 > alpha1 {
+        for i in 1..7 {
+            let a = ((7 - i) * alpha0 as u32 + i * alpha1 as u32) as f32 / 7.0;
+            palette.push([a, 0.0, 0.0, 0.0]);
+        }
+    }
+
+    let mut indexes = 0u64;
+    for (i, p) in block.iter().enumerate() {
+        let index = nearest(&palette, [p[3] as f32, 0.0, 0.0, 0.0]) as u64;
+        indexes |= index << (i * 3);
+    }
+
+    let mut bytes = [0u8; 8];
+    bytes[0] = alpha0;
+    bytes[1] = alpha1;
+    bytes[2..8].copy_from_slice(&indexes.to_le_bytes()[0..6]);
+
+    bytes
+}
+
+/// Compresses a block to BC7, using mode 6.
+/// Mode 6 has a single pair of RGBA endpoints with 4 bit indexes, which suits blocks with smooth alpha.
+fn bc7(block: &[[u8; 4]; 16]) -> [u8; 16] {
+    let colors: Vec<[f32; 4]> = block
+        .iter()
+        .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32, p[3] as f32])
+        .collect();
+
+    let (start, end) = endpoints(&colors);
+    let mut endpoint0 = bc7_endpoint(start);
+    let mut endpoint1 = bc7_endpoint(end);
+
+    let unquantize = |(e, p): ([u8; 4], u8)| {
+        [
+            (e[0] << 1 | p) as u32,
+            (e[1] << 1 | p) as u32,
+            (e[2] << 1 | p) as u32,
+            (e[3] << 1 | p) as u32,
+        ]
+    };
+    let (c0, c1) = (unquantize(endpoint0), unquantize(endpoint1));
+
+    let palette: Vec<[f32; 4]> = BC7_WEIGHTS
+        .iter()
+        .map(|w| {
+            let mut color = [0.0; 4];
+            for c in 0..4 {
+                color[c] = (((64 - w) * c0[c] + w * c1[c] + 32) >> 6) as f32;
+            }
+
+            color
+        })
+        .collect();
+
+    let mut indexes: Vec<u32> = colors.iter().map(|c| nearest(&palette, *c)).collect();
+
+    // The first index only has 3 bits, so swap the endpoints if it needs the 4th
+    if indexes[0] >= 8 {
+        std::mem::swap(&mut endpoint0, &mut endpoint1);
+        for index in indexes.iter_mut() {
+            *index = 15 - *index;
+        }
+    }
+
+    let mut bits = Bits::default();
+
+    // Mode 6 is 6 zero bits followed by a one
+    bits.push(1 << 6, 7);
+    for c in 0..4 {
+        bits.push(endpoint0.0[c] as u32, 7);
+        bits.push(endpoint1.0[c] as u32, 7);
+    }
+    bits.push(endpoint0.1 as u32, 1);
+    bits.push(endpoint1.1 as u32, 1);
+
+    for (i, index) in indexes.iter().enumerate() {
+        bits.push(*index, if i == 0 { 3 } else { 4 });
+    }
+
+    bits.value.to_le_bytes()
+}
+
+/// Quantizes a BC7 mode 6 endpoint to 7 bits per channel, plus the low bit shared by every channel.
+fn bc7_endpoint(color: [f32; 4]) -> ([u8; 4], u8) {
+    let mut best = ([0u8; 4], 0u8, f32::MAX);
+
+    for p in 0..2u8 {
+        let mut endpoint = [0u8; 4];
+        let mut error = 0.0;
+        for c in 0..4 {
+            let q = ((color[c] - p as f32) / 2.0).round().clamp(0.0, 127.0);
+            endpoint[c] = q as u8;
+            error += (q * 2.0 + p as f32 - color[c]).powi(2);
+        }
+
+        if error < best.2 {
+            best = (endpoint, p, error);
+        }
+    }
+
+    (best.0, best.1)
+}
+
+/// Fits a line through the colors, returning the ends of the range they cover along it.
+fn endpoints(colors: &[[f32; 4]]) -> ([f32; 4], [f32; 4]) {
+    let n = colors.len() as f32;
+
+    let mut mean = [0.0f32; 4];
+    for color in colors.iter() {
+        for c in 0..4 {
+            mean[c] += color[c] / n;
+        }
+    }
+
+    let mut covariance = [[0.0f32; 4]; 4];
+    for color in colors.iter() {
+        for i in 0..4 {
+            for j in 0..4 {
+                covariance[i][j] += (color[i] - mean[i]) * (color[j] - mean[j]);
+            }
+        }
+    }
+
+    // Power iteration finds the direction the colors vary the most in
+    let mut axis = [1.0f32; 4];
+    for _ in 0..8 {
+        let mut next = [0.0f32; 4];
+        for i in 0..4 {
+            for j in 0..4 {
+                next[i] += covariance[i][j] * axis[j];
+            }
+        }
+
+        let length = next.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length < f32::EPSILON {
+            // Every color is the same
+            return (mean, mean);
+        }
+
+        for c in 0..4 {
+            axis[c] = next[c] / length;
+        }
+    }
+
+    let project = |color: &[f32; 4]| (0..4).map(|c| (color[c] - mean[c]) * axis[c]).sum::<f32>();
+    let (min, max) = colors
+        .iter()
+        .map(project)
+        .fold((f32::MAX, f32::MIN), |(min, max), t| {
+            (min.min(t), max.max(t))
+        });
+
+    let at = |t: f32| {
+        let mut color = [0.0f32; 4];
+        for c in 0..4 {
+            color[c] = (mean[c] + axis[c] * t).clamp(0.0, 255.0);
+        }
+
+        color
+    };
+
+    (at(min), at(max))
+}
+
+/// Returns the index of the closest color in the palette.
+fn nearest(palette: &[[f32; 4]], color: [f32; 4]) -> u32 {
+    let mut nearest = 0;
+    let mut nearest_distance = f32::MAX;
+
+    for (idx, p) in palette.iter().enumerate() {
+        let distance: f32 = (0..4).map(|c| (color[c] - p[c]).powi(2)).sum();
+        if distance < nearest_distance {
+            nearest = idx as u32;
+            nearest_distance = distance;
+        }
+    }
+
+    nearest
+}
+
+fn lerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut color = [0.0f32; 4];
+    for c in 0..4 {
+        color[c] = a[c] + (b[c] - a[c]) * t;
+    }
+
+    color
+}
+
+fn to_565(color: [f32; 4]) -> u16 {
+    let r = (color[0] * 31.0 / 255.0).round() as u16;
+    let g = (color[1] * 63.0 / 255.0).round() as u16;
+    let b = (color[2] * 31.0 / 255.0).round() as u16;
+
+    r << 11 | g << 5 | b
+}
+
+fn from_565(color: u16) -> [f32; 4] {
+    let r = (color >> 11 & 31) as u8;
+    let g = (color >> 5 & 63) as u8;
+    let b = (color & 31) as u8;
+
+    [
+        (r << 3 | r >> 2) as f32,
+        (g << 2 | g >> 4) as f32,
+        (b << 3 | b >> 2) as f32,
+        0.0,
+    ]
+}
+
+/// Writes values into a block, starting from the lowest bit.
+#[derive(Default)]
+struct Bits {
+    value: u128,
+    len: u32,
+}
+
+impl Bits {
+    fn push(&mut self, value: u32, bits: u32) {
+        self.value |= (value as u128) << self.len;
+        self.len += bits;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    /// A block blending between two colors, with alpha going down by row.
+    fn gradient_block() -> [[u8; 4]; 16] {
+        let mut block = [[0u8; 4]; 16];
+        for (i, p) in block.iter_mut().enumerate() {
+            let t = (i % 4) as f32 / 3.0;
+            *p = [
+                (16.0 + 200.0 * t) as u8,
+                (80.0 + 100.0 * t) as u8,
+                (240.0 - 160.0 * t) as u8,
+                255 - (i / 4) as u8 * 60,
+            ];
+        }
+
+        block
+    }
+
+    /// Decodes a BC1 block, as a GPU would.
+    fn decode_bc1(bytes: &[u8]) -> [[u8; 4]; 16] {
+        let color0 = u16::from_le_bytes([bytes[0], bytes[1]]);
+        let color1 = u16::from_le_bytes([bytes[2], bytes[3]]);
+        let (c0, c1) = (from_565(color0), from_565(color1));
+
+        let palette = if color0 > color1 {
+            [c0, c1, lerp(c0, c1, 1.0 / 3.0), lerp(c0, c1, 2.0 / 3.0)]
+        } else {
+            [c0, c1, lerp(c0, c1, 0.5), [0.0; 4]]
+        };
+
+        let indexes = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        let mut block = [[0u8; 4]; 16];
+        for (i, p) in block.iter_mut().enumerate() {
+            let index = (indexes >> (i * 2) & 3) as usize;
+            let alpha = if color0 <= color1 && index == 3 {
+                0
+            } else {
+                255
+            };
+            let c = palette[index];
+            *p = [
+                c[0].round() as u8,
+                c[1].round() as u8,
+                c[2].round() as u8,
+                alpha,
+            ];
+        }
+
+        block
+    }
+
+    /// Decodes the alpha half of a BC3 block.
+    fn decode_bc3_alpha(bytes: &[u8]) -> [u8; 16] {
+        let (alpha0, alpha1) = (bytes[0] as u32, bytes[1] as u32);
+        let mut palette = vec![alpha0, alpha1];
+        if alpha0 > alpha1 {
+            for i in 1..7 {
+                palette.push(((7 - i) * alpha0 + i * alpha1) / 7);
+            }
+        } else {
+            for i in 1..5 {
+                palette.push(((5 - i) * alpha0 + i * alpha1) / 5);
+            }
+            palette.extend_from_slice(&[0, 255]);
+        }
+
+        let mut index_bytes = [0u8; 8];
+        index_bytes[0..6].copy_from_slice(&bytes[2..8]);
+        let indexes = u64::from_le_bytes(index_bytes);
+
+        let mut alphas = [0u8; 16];
+        for (i, a) in alphas.iter_mut().enumerate() {
+            *a = palette[(indexes >> (i * 3) & 7) as usize] as u8;
+        }
+
+        alphas
+    }
+
+    /// Decodes a BC7 mode 6 block.
+    fn decode_bc7_mode_6(bytes: &[u8]) -> [[u8; 4]; 16] {
+        let mut value = u128::from_le_bytes(bytes.try_into().unwrap());
+        let mut read = |bits: u32| {
+            let v = (value & ((1 << bits) - 1)) as u32;
+            value >>= bits;
+            v
+        };
+
+        assert_eq!(1 << 6, read(7));
+
+        // Each channel of both endpoints, then the low bit of each endpoint
+        let mut channels = [[0u32; 2]; 4];
+        for channel in channels.iter_mut() {
+            *channel = [read(7), read(7)];
+        }
+        let low_bits = [read(1), read(1)];
+
+        let mut block = [[0u8; 4]; 16];
+        for (i, p) in block.iter_mut().enumerate() {
+            let w = BC7_WEIGHTS[read(if i == 0 { 3 } else { 4 }) as usize];
+            for (v, channel) in p.iter_mut().zip(channels.iter()) {
+                let (e0, e1) = (channel[0] << 1 | low_bits[0], channel[1] << 1 | low_bits[1]);
+                *v = (((64 - w) * e0 + w * e1 + 32) >> 6) as u8;
+            }
+        }
+
+        block
+    }
+
+    fn assert_close(
+        expected: &[[u8; 4]; 16],
+        actual: &[[u8; 4]; 16],
+        channels: usize,
+        tolerance: i32,
+    ) {
+        for (e, a) in expected.iter().zip(actual.iter()) {
+            for c in 0..channels {
+                assert!(
+                    (e[c] as i32 - a[c] as i32).abs() <= tolerance,
+                    "expected {:?}, got {:?}",
+                    e,
+                    a
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bc1_round_trips() {
+        let block = gradient_block();
+        let decoded = decode_bc1(&bc1(&block, false));
+
+        assert_close(&block, &decoded, 3, 12);
+        assert!(decoded.iter().all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn bc1_punch_through_keeps_transparent_pixels() {
+        let mut block = gradient_block();
+        for p in block.iter_mut().take(4) {
+            p[3] = 0;
+        }
+        for p in block.iter_mut().skip(4) {
+            p[3] = 255;
+        }
+
+        let decoded = decode_bc1(&bc1(&block, true));
+
+        for (p, d) in block.iter().zip(decoded.iter()) {
+            assert_eq!(p[3], d[3]);
+            // The 3 color mode only has a color halfway between the endpoints
+            if p[3] == 255 {
+                assert_close(&[*p; 16], &[*d; 16], 3, 40);
+            }
+        }
+
+        assert_eq!(
+            [0, 0, 0, 0, 255, 255, 255, 255],
+            bc1(&[[9, 9, 9, 0]; 16], true)
+        );
+    }
+
+    #[test]
+    fn bc3_alpha_round_trips() {
+        let block = gradient_block();
+        let decoded = decode_bc3_alpha(&bc3_alpha(&block));
+
+        for (p, a) in block.iter().zip(decoded.iter()) {
+            assert!((p[3] as i32 - *a as i32).abs() <= 255 / 14);
+        }
+    }
+
+    #[test]
+    fn bc7_round_trips() {
+        // Mode 6 has a single pair of endpoints, so alpha changes along with the colors
+        let mut block = gradient_block();
+        for (i, p) in block.iter_mut().enumerate() {
+            p[3] = 255 - (i % 4) as u8 * 50;
+        }
+
+        let decoded = decode_bc7_mode_6(&bc7(&block));
+
+        assert_close(&block, &decoded, 4, 24);
+    }
+
+    #[test]
+    fn execute_writes_a_block_for_every_4x4_pixels() {
+        // Partial blocks on the right and bottom still take a whole block
+        let img = RgbaImage::from_pixel(6, 5, image::Rgba([40, 80, 120, 255]));
+
+        assert_eq!(4 * 8, execute(&img, BlockCompression::Bc1).len());
+        assert_eq!(4 * 16, execute(&img, BlockCompression::Bc3).len());
+        assert_eq!(4 * 16, execute(&img, BlockCompression::Bc7).len());
+
+        let decoded = decode_bc1(&execute(&img, BlockCompression::Bc1)[24..32]);
+        assert_close(&[[40, 80, 120, 255]; 16], &decoded, 4, 4);
+    }
+
+    #[test]
+    fn mip_chain_halves_down_to_1x1() {
+        let img = RgbaImage::new(8, 2);
+        let sizes: Vec<(u32, u32)> = mip_chain(&img).iter().map(|l| l.dimensions()).collect();
+
+        assert_eq!(vec![(8, 2), (4, 1), (2, 1), (1, 1)], sizes);
+    }
+}
@@ -0,0 +1,256 @@
+use crate::cfg::CropPolicy;
+use crate::internal_sheet::{Animation, Frame};
+use benchy::Benchy;
+
+/// The (start x, start y, end x, end y) of a frame in the render.
+type Bounds = (u32, u32, u32, u32);
+
+/// Sets each frame's bounds to the bounds it shares with the other frames in its group.
+/// Frames start out with their own bounds, so those are kept for the frame policy. Empty frames are left empty.
+/// Frames are rendered again and cropped to the shared bounds, so they keep any faint pixels that fall inside them.
+pub fn execute(animations: &mut [Animation], policy: CropPolicy) {
+    Benchy::time("crop");
+
+    match policy {
+        CropPolicy::Frame => {}
+        CropPolicy::Direction => {
+            for animation in animations.iter_mut() {
+                for direction in animation.directional_animations.iter_mut() {
+                    let bounds = union(direction.frames.iter());
+                    share(direction.frames.iter_mut(), bounds);
+                }
+            }
+        }
+        CropPolicy::Animation => {
+            for animation in animations.iter_mut() {
+                let bounds = union(
+                    animation
+                        .directional_animations
+                        .iter()
+                        .flat_map(|d| d.frames.iter()),
+                );
+
+                share(
+                    animation
+                        .directional_animations
+                        .iter_mut()
+                        .flat_map(|d| d.frames.iter_mut()),
+                    bounds,
+                );
+            }
+        }
+        CropPolicy::Sheet => {
+            let bounds = union(
+                animations
+                    .iter()
+                    .flat_map(|a| a.directional_animations.iter())
+                    .flat_map(|d| d.frames.iter()),
+            );
+
+            share(
+                animations
+                    .iter_mut()
+                    .flat_map(|a| a.directional_animations.iter_mut())
+                    .flat_map(|d| d.frames.iter_mut()),
+                bounds,
+            );
+        }
+    }
+}
+
+/// Returns the bounds covering all of the frames.
+fn union<'a, I>(frames: I) -> Option<Bounds>
+where
+    I: Iterator<Item = &'a Frame>,
+{
+    frames.filter(|f| !f.empty).fold(None, |bounds, f| {
+        let (start_x, start_y) = (f.offset_x_px, f.offset_y_px);
+        let (end_x, end_y) = (start_x + f.width_px, start_y + f.height_px);
+
+        Some(match bounds {
+            Some((x0, y0, x1, y1)) => (
+                x0.min(start_x),
+                y0.min(start_y),
+                x1.max(end_x),
+                y1.max(end_y),
+            ),
+            None => (start_x, start_y, end_x, end_y),
+        })
+    })
+}
+
+/// Sets each frame's bounds to the given shared bounds.
+fn share<'a, I>(frames: I, bounds: Option<Bounds>)
+where
+    I: Iterator<Item = &'a mut Frame>,
+{
+    let (start_x, start_y, end_x, end_y) = match bounds {
+        Some(bounds) => bounds,
+        None => return,
+    };
+
+    for frame in frames.filter(|f| !f.empty) {
+        frame.offset_x_px = start_x;
+        frame.offset_y_px = start_y;
+        frame.width_px = end_x - start_x;
+        frame.height_px = end_y - start_y;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_sheet::DirectionalAnimation;
+
+    /// The (x, y, w, h) of a frame in the render.
+    type Rect = (u32, u32, u32, u32);
+
+    fn frame(bounds: Rect) -> Frame {
+        let (x, y, w, h) = bounds;
+
+        Frame {
+            duration_ms: 100,
+            empty: false,
+            grid_cell: None,
+            height_px: h,
+            offset_x_px: x,
+            offset_y_px: y,
+            page: 0,
+            pivot_px: None,
+            rotated: false,
+            source_w_px: 64,
+            source_h_px: 64,
+            start_x_px: 0,
+            start_y_px: 0,
+            width_px: w,
+        }
+    }
+
+    fn empty_frame() -> Frame {
+        Frame {
+            empty: true,
+            ..frame((0, 0, 0, 0))
+        }
+    }
+
+    fn animation(directions: Vec<Vec<Frame>>) -> Animation {
+        Animation {
+            directional_animations: directions
+                .into_iter()
+                .enumerate()
+                .map(|(direction, frames)| DirectionalAnimation {
+                    direction: direction as u32,
+                    frames,
+                    page: 0,
+                    start_x_px: 0,
+                    start_y_px: 0,
+                })
+                .collect(),
+            name: "idle".to_string(),
+            fps: 24.0,
+            frame_step: 1,
+            looping: true,
+            page: 0,
+            start_x_px: 0,
+            start_y_px: 0,
+        }
+    }
+
+    /// Two animations, with an empty frame in the middle of the first direction.
+    fn animations() -> Vec<Animation> {
+        vec![
+            animation(vec![
+                vec![frame((10, 20, 5, 5)), empty_frame(), frame((12, 18, 6, 4))],
+                vec![frame((30, 30, 2, 2))],
+            ]),
+            animation(vec![vec![frame((4, 40, 3, 10))]]),
+        ]
+    }
+
+    /// The rect of every frame, in order.
+    fn bounds(animations: &[Animation]) -> Vec<Vec<Vec<Rect>>> {
+        animations
+            .iter()
+            .map(|a| {
+                a.directional_animations
+                    .iter()
+                    .map(|d| {
+                        d.frames
+                            .iter()
+                            .map(|f| (f.offset_x_px, f.offset_y_px, f.width_px, f.height_px))
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn frame_policy_keeps_each_frames_bounds() {
+        let mut cropped = animations();
+        execute(&mut cropped, CropPolicy::Frame);
+
+        assert_eq!(animations(), cropped);
+    }
+
+    #[test]
+    fn direction_policy_shares_bounds_in_each_direction() {
+        let mut animations = animations();
+        execute(&mut animations, CropPolicy::Direction);
+
+        assert_eq!(
+            vec![
+                vec![
+                    vec![(10, 18, 8, 7), (0, 0, 0, 0), (10, 18, 8, 7)],
+                    vec![(30, 30, 2, 2)],
+                ],
+                vec![vec![(4, 40, 3, 10)]],
+            ],
+            bounds(&animations)
+        );
+    }
+
+    #[test]
+    fn animation_policy_shares_bounds_in_each_animation() {
+        let mut animations = animations();
+        execute(&mut animations, CropPolicy::Animation);
+
+        assert_eq!(
+            vec![
+                vec![
+                    vec![(10, 18, 22, 14), (0, 0, 0, 0), (10, 18, 22, 14)],
+                    vec![(10, 18, 22, 14)],
+                ],
+                vec![vec![(4, 40, 3, 10)]],
+            ],
+            bounds(&animations)
+        );
+    }
+
+    #[test]
+    fn sheet_policy_shares_bounds_across_animations() {
+        let mut animations = animations();
+        execute(&mut animations, CropPolicy::Sheet);
+
+        let shared = (4, 18, 28, 32);
+        assert_eq!(
+            vec![
+                vec![vec![shared, (0, 0, 0, 0), shared], vec![shared]],
+                vec![vec![shared]],
+            ],
+            bounds(&animations)
+        );
+        assert!(animations[0].directional_animations[0].frames[1].empty);
+    }
+
+    #[test]
+    fn empty_directions_are_left_empty() {
+        let mut animations = vec![animation(vec![vec![empty_frame(), empty_frame()]])];
+        execute(&mut animations, CropPolicy::Direction);
+
+        assert_eq!(
+            vec![animation(vec![vec![empty_frame(), empty_frame()]])],
+            animations
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::cfg::DownsampleFilter;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+
+/// Shrinks a render made at the given scale back down to the sprite size.
+pub fn execute(img: &RgbaImage, scale: u32, filter: DownsampleFilter) -> RgbaImage {
+    let (w, h) = (img.width() / scale, img.height() / scale);
+
+    match filter {
+        DownsampleFilter::Box => RgbaImage::from_fn(w, h, |x, y| {
+            // Weight colors by their alpha so the transparent background doesn't darken the edges
+            let mut sum = [0u64; 3];
+            let mut alpha = 0u64;
+            for p in block(img, scale, x, y) {
+                let a = p[3] as u64;
+                for c in 0..3 {
+                    sum[c] += p[c] as u64 * a;
+                }
+                alpha += a;
+            }
+
+            if alpha == 0 {
+                return Rgba([0, 0, 0, 0]);
+            }
+
+            let n = (scale * scale) as u64;
+            Rgba([
+                (sum[0] / alpha) as u8,
+                (sum[1] / alpha) as u8,
+                (sum[2] / alpha) as u8,
+                (alpha / n) as u8,
+            ])
+        }),
+        DownsampleFilter::Lanczos => {
+            // Filter in premultiplied space for the same reason
+            let mut premultiplied = img.clone();
+            for p in premultiplied.pixels_mut() {
+                for c in 0..3 {
+                    p[c] = (p[c] as u32 * p[3] as u32 / 255) as u8;
+                }
+            }
+
+            let mut resized = imageops::resize(&premultiplied, w, h, FilterType::Lanczos3);
+            for p in resized.pixels_mut() {
+                if p[3] != 0 {
+                    for c in 0..3 {
+                        p[c] = (p[c] as u32 * 255 / p[3] as u32).min(255) as u8;
+                    }
+                }
+            }
+
+            resized
+        }
+        DownsampleFilter::Nearest => RgbaImage::from_fn(w, h, |x, y| {
+            *img.get_pixel(x * scale + scale / 2, y * scale + scale / 2)
+        }),
+        DownsampleFilter::Majority => RgbaImage::from_fn(w, h, |x, y| {
+            // The most common color wins, with ties going to the first to get there
+            let mut counts: HashMap<Rgba<u8>, u32> = HashMap::new();
+            let mut majority = (Rgba([0, 0, 0, 0]), 0);
+            for p in block(img, scale, x, y) {
+                // Everything transparent is the same color
+                let p = if p[3] == 0 { Rgba([0, 0, 0, 0]) } else { *p };
+
+                let count = counts.entry(p).or_default();
+                *count += 1;
+
+                if *count > majority.1 {
+                    majority = (p, *count);
+                }
+            }
+
+            majority.0
+        }),
+    }
+}
+
+/// Returns the pixels of the render that make up the downsampled pixel at (x, y).
+fn block<'a>(img: &'a RgbaImage, scale: u32, x: u32, y: u32) -> impl Iterator<Item = &'a Rgba<u8>> {
+    (0..scale * scale).map(move |i| img.get_pixel(x * scale + i % scale, y * scale + i / scale))
+}
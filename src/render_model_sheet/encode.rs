@@ -0,0 +1,111 @@
+use super::palette::Palette;
+use super::texture_container::{self, CompressedTexture};
+use super::{alpha, block_compress, quantize};
+use crate::cfg::{DitherKind, TextureFormat};
+use crate::internal_sheet::{self, SpriteSheet};
+use crate::Cfg;
+use benchy::Benchy;
+use image::{DynamicImage, RgbaImage};
+
+/// Encodes each page of the sheet as a PNG, restricting it to the palette if there is one.
+/// Extra passes are data rather than colors, so they're always left as is.
+/// Pages are also block compressed into a texture container, if the config has one.
+pub fn execute(sprite_sheet: &mut SpriteSheet, cfg: &Cfg, palette: Option<&Palette>) {
+    Benchy::time("encode");
+
+    for page in sprite_sheet.pages.iter_mut() {
+        let (w, h) = (page.width_px, page.height_px);
+
+        match palette {
+            Some(palette) => {
+                let indexes = quantize::execute(&page.image, palette, cfg.dither);
+
+                // Quantized pixels are either opaque or black and transparent, so they're already premultiplied
+                page.image = quantize::to_rgba(w, h, &indexes, palette);
+                page.image_png_bytes = quantize::indexed_png(w, h, &indexes, palette);
+            }
+            None => {
+                if cfg.premultiply_alpha {
+                    alpha::premultiply(&mut page.image);
+                }
+
+                page.image_png_bytes = rgba_png(&page.image);
+            }
+        }
+    }
+
+    for layer in sprite_sheet.layers.iter_mut() {
+        for page in layer.pages.iter_mut() {
+            page.image_png_bytes = rgba_png(&page.image);
+        }
+    }
+
+    if let Some(lut) = sprite_sheet.team_mask.as_mut().and_then(|t| t.lut.as_mut()) {
+        lut.image_png_bytes = rgba_png(&lut.image);
+    }
+
+    sprite_sheet.premultiplied_alpha = cfg.premultiply_alpha;
+
+    if cfg.texture_format != TextureFormat::Png {
+        for page in sprite_sheet.pages.iter_mut() {
+            page.texture_bytes = texture(&page.image, cfg, true);
+        }
+
+        for layer in sprite_sheet.layers.iter_mut() {
+            for page in layer.pages.iter_mut() {
+                page.texture_bytes = texture(&page.image, cfg, false);
+            }
+        }
+
+        sprite_sheet.texture = Some(internal_sheet::Texture {
+            format: cfg.texture_format,
+            compression: cfg.block_compression,
+            mipmaps: cfg.mipmaps,
+        });
+    }
+}
+
+/// Block compresses the image, along with its mip chain if enabled, into the texture container.
+fn texture(image: &RgbaImage, cfg: &Cfg, srgb: bool) -> Vec<u8> {
+    let levels = if cfg.mipmaps {
+        block_compress::mip_chain(image)
+    } else {
+        vec![image.clone()]
+    };
+
+    let texture = CompressedTexture {
+        width_px: image.width(),
+        height_px: image.height(),
+        compression: cfg.block_compression,
+        srgb,
+        premultiplied_alpha: cfg.premultiply_alpha,
+        levels: levels
+            .iter()
+            .map(|level| block_compress::execute(level, cfg.block_compression))
+            .collect(),
+    };
+
+    texture_container::execute(&texture, cfg.texture_format)
+}
+
+/// Encodes a copy of a page that's already been encoded, such as one an exporter rearranged, the same way as the page.
+/// Its pixels are already in the palette and premultiplied if they need to be, so only the PNG is made.
+pub fn page_png(image: &RgbaImage, palette: Option<&Palette>) -> Vec<u8> {
+    match palette {
+        Some(palette) => {
+            let indexes = quantize::execute(image, palette, DitherKind::None);
+            quantize::indexed_png(image.width(), image.height(), &indexes, palette)
+        }
+        None => rgba_png(image),
+    }
+}
+
+/// Encodes the image as an RGBA PNG.
+pub fn rgba_png(image: &RgbaImage) -> Vec<u8> {
+    let mut image_png_bytes: Vec<u8> = Vec::new();
+    DynamicImage::ImageRgba8(image.clone())
+        .write_to(&mut image_png_bytes, image::ImageOutputFormat::Png)
+        .unwrap();
+
+    image_png_bytes
+}
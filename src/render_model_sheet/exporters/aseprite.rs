@@ -0,0 +1,145 @@
+use super::{texture_packer, write_pages, Exporter, Palette};
+use crate::internal_sheet::SpriteSheet;
+use crate::Cfg;
+use benchy::Benchy;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::prelude::*;
+
+/// Aseprite's JSON sheet, with a frame tag for each animation direction.
+/// Aseprite sheets only have a single image, so frames on later pages note their page and the images are listed in
+/// the metadata. Only the color pages are exported.
+pub struct Aseprite;
+
+impl Exporter for Aseprite {
+    fn export(
+        &self,
+        path: &str,
+        file_name: &str,
+        sprite_sheet: &SpriteSheet,
+        _cfg: &Cfg,
+        _palette: Option<&Palette>,
+    ) {
+        Benchy::time("export_aseprite");
+
+        let images = write_pages(path, file_name, &sprite_sheet.pages);
+
+        let mut frames: Vec<Value> = vec![];
+        let mut frame_tags: Vec<Value> = vec![];
+
+        for animation in sprite_sheet.animations.iter() {
+            for direction in animation.directional_animations.iter() {
+                if direction.frames.is_empty() {
+                    continue;
+                }
+
+                let from = frames.len();
+
+                for (frame_idx, frame) in direction.frames.iter().enumerate() {
+                    let mut frame_json = texture_packer::frame_json(frame);
+                    frame_json["filename"] =
+                        format!("{}/{}/{}", animation.name, direction.direction, frame_idx).into();
+                    frame_json["duration"] = frame.duration_ms.into();
+
+                    if sprite_sheet.pages.len() > 1 {
+                        frame_json["page"] = frame.page.into();
+                    }
+
+                    frames.push(frame_json);
+                }
+
+                let mut tag = json!({
+                    "name": format!("{}_{}", animation.name, direction.direction),
+                    "from": from,
+                    "to": frames.len() - 1,
+                    "direction": "forward",
+                });
+
+                // Aseprite repeats tags forever unless told otherwise
+                if !animation.looping {
+                    tag["repeat"] = "1".into();
+                }
+
+                frame_tags.push(tag);
+            }
+        }
+
+        let first_page = &sprite_sheet.pages[0];
+        let mut json = json!({
+            "frames": frames,
+            "meta": {
+                "app": env!("CARGO_PKG_NAME"),
+                "version": "1.0",
+                "image": images[0],
+                "format": "RGBA8888",
+                "size": { "w": first_page.width_px, "h": first_page.height_px },
+                "scale": "1",
+                "frameTags": frame_tags,
+                "layers": [],
+                "slices": [],
+            },
+        });
+
+        if images.len() > 1 {
+            json["meta"]["images"] = images.into();
+        }
+
+        let mut file = File::create(format!("{}{}_aseprite.json", path, file_name)).unwrap();
+        file.write_all(serde_json::to_string(&json).unwrap().as_bytes())
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_sheet;
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn exports_frames_and_tags() {
+        let path = test_sheet::export(&Aseprite, &test_sheet::sheet(), "aseprite");
+        let json = fs::read_to_string(path.join("test_aseprite.json")).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        let expected = json!({
+            "frames": [
+                {
+                    "filename": "walk/0/0",
+                    "frame": { "x": 1, "y": 1, "w": 5, "h": 4 },
+                    "rotated": false,
+                    "trimmed": true,
+                    "spriteSourceSize": { "x": 2, "y": 3, "w": 5, "h": 4 },
+                    "sourceSize": { "w": 12, "h": 10 },
+                    "pivot": { "x": 0.5, "y": 0.75 },
+                    "duration": 100,
+                },
+                {
+                    "filename": "walk/0/1",
+                    "frame": { "x": 8, "y": 1, "w": 6, "h": 3 },
+                    "rotated": true,
+                    "trimmed": false,
+                    "spriteSourceSize": { "x": 0, "y": 0, "w": 6, "h": 3 },
+                    "sourceSize": { "w": 6, "h": 3 },
+                    "duration": 150,
+                },
+            ],
+            "meta": {
+                "app": env!("CARGO_PKG_NAME"),
+                "version": "1.0",
+                "image": "test.png",
+                "format": "RGBA8888",
+                "size": { "w": 16, "h": 16 },
+                "scale": "1",
+                // The animation doesn't loop, so it only plays once
+                "frameTags": [
+                    { "name": "walk_0", "from": 0, "to": 1, "direction": "forward", "repeat": "1" },
+                ],
+                "layers": [],
+                "slices": [],
+            },
+        });
+
+        assert_eq!(expected, serde_json::from_str::<Value>(&json).unwrap());
+    }
+}
@@ -0,0 +1,185 @@
+use super::{write_pages, Exporter, Palette};
+use crate::internal_sheet::{Frame, SpriteSheet};
+use crate::Cfg;
+use benchy::Benchy;
+use std::fs::File;
+use std::io::prelude::*;
+
+/// A Godot 4 `SpriteFrames` resource, with an animation for each animation direction.
+/// Frames are `AtlasTexture` regions of the pages, with margins restoring the trimmed pixels so every frame is its
+/// source size. Only the color pages are exported.
+pub struct Godot;
+
+impl Exporter for Godot {
+    fn export(
+        &self,
+        path: &str,
+        file_name: &str,
+        sprite_sheet: &SpriteSheet,
+        _cfg: &Cfg,
+        _palette: Option<&Palette>,
+    ) {
+        Benchy::time("export_godot");
+
+        let images = write_pages(path, file_name, &sprite_sheet.pages);
+
+        // Step 1: reference each page, relative to the resource
+        let mut ext_resources = String::new();
+        for (page_idx, image) in images.iter().enumerate() {
+            ext_resources.push_str(&format!(
+                "[ext_resource type=\"Texture2D\" path=\"{}\" id=\"{}\"]\n",
+                image,
+                page_id(page_idx)
+            ));
+        }
+
+        // Step 2: make a region of the page for each frame, along with the animations using them
+        let mut sub_resources = String::new();
+        let mut num_sub_resources = 0;
+        let mut animations = vec![];
+
+        for animation in sprite_sheet.animations.iter() {
+            // Godot's speed is in frames per second, with each frame's duration relative to it
+            let speed = animation.fps / animation.frame_step.max(1) as f32;
+
+            for direction in animation.directional_animations.iter() {
+                let mut frames = vec![];
+
+                for frame in direction.frames.iter() {
+                    let duration = relative_duration(frame, speed);
+
+                    // Empty frames keep their place without drawing anything
+                    if frame.empty {
+                        frames.push(format!(
+                            "{{\n\"duration\": {:?},\n\"texture\": null\n}}",
+                            duration
+                        ));
+                        continue;
+                    }
+
+                    if frame.rotated {
+                        panic!(
+                            "Godot's AtlasTexture can't rotate regions, so exporting '{}' for Godot needs allow_rotation to be false.",
+                            file_name
+                        );
+                    }
+
+                    let id = format!("AtlasTexture_{}", num_sub_resources);
+                    num_sub_resources += 1;
+
+                    sub_resources.push_str(&atlas_texture(&id, frame));
+                    frames.push(format!(
+                        "{{\n\"duration\": {:?},\n\"texture\": SubResource(\"{}\")\n}}",
+                        duration, id
+                    ));
+                }
+
+                if frames.is_empty() {
+                    continue;
+                }
+
+                animations.push(format!(
+                    "{{\n\"frames\": [{}],\n\"loop\": {},\n\"name\": &\"{}_{}\",\n\"speed\": {:?}\n}}",
+                    frames.join(", "),
+                    animation.looping,
+                    animation.name,
+                    direction.direction,
+                    speed
+                ));
+            }
+        }
+
+        // Step 3: write the resource
+        let load_steps = images.len() + num_sub_resources + 1;
+        let tres = format!(
+            "[gd_resource type=\"SpriteFrames\" load_steps={} format=3]\n\n{}\n{}[resource]\nanimations = [{}]\n",
+            load_steps,
+            ext_resources,
+            sub_resources,
+            animations.join(", ")
+        );
+
+        let mut file = File::create(format!("{}{}.tres", path, file_name)).unwrap();
+        file.write_all(tres.as_bytes()).unwrap();
+    }
+}
+
+/// The id of a page's texture in the resource.
+fn page_id(page_idx: usize) -> String {
+    format!("{}_page", page_idx + 1)
+}
+
+/// Writes the frame's region of its page.
+/// The margin's position is the trim offset, and its size is how much larger the source is than the region.
+fn atlas_texture<'a>(id: &'a str, frame: &Frame) -> String {
+    format!(
+        "[sub_resource type=\"AtlasTexture\" id=\"{}\"]\natlas = ExtResource(\"{}\")\nregion = Rect2({}, {}, {}, {})\nmargin = Rect2({}, {}, {}, {})\n\n",
+        id,
+        page_id(frame.page as usize),
+        frame.start_x_px,
+        frame.start_y_px,
+        frame.width_px,
+        frame.height_px,
+        frame.offset_x_px,
+        frame.offset_y_px,
+        frame.source_w_px - frame.width_px,
+        frame.source_h_px - frame.height_px
+    )
+}
+
+/// Returns how long the frame is shown for, as a multiple of the animation's frame length.
+/// Rounded to tenths, as the frame's duration is only to the millisecond.
+fn relative_duration(frame: &Frame, speed: f32) -> f32 {
+    let duration = frame.duration_ms as f32 * speed / 1000.0;
+
+    (duration * 10.0).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_sheet;
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn exports_sprite_frames() {
+        // Godot exports can't have rotated frames
+        let mut sprite_sheet = test_sheet::sheet();
+        sprite_sheet.animations[0].directional_animations[0].frames[1].rotated = false;
+
+        let path = test_sheet::export(&Godot, &sprite_sheet, "godot");
+        let tres = fs::read_to_string(path.join("test.tres")).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        let expected = r#"[gd_resource type="SpriteFrames" load_steps=4 format=3]
+
+[ext_resource type="Texture2D" path="test.png" id="1_page"]
+
+[sub_resource type="AtlasTexture" id="AtlasTexture_0"]
+atlas = ExtResource("1_page")
+region = Rect2(1, 1, 5, 4)
+margin = Rect2(2, 3, 7, 6)
+
+[sub_resource type="AtlasTexture" id="AtlasTexture_1"]
+atlas = ExtResource("1_page")
+region = Rect2(8, 1, 6, 3)
+margin = Rect2(0, 0, 0, 0)
+
+[resource]
+animations = [{
+"frames": [{
+"duration": 1.0,
+"texture": SubResource("AtlasTexture_0")
+}, {
+"duration": 1.5,
+"texture": SubResource("AtlasTexture_1")
+}],
+"loop": false,
+"name": &"walk_0",
+"speed": 10.0
+}]
+"#;
+
+        assert_eq!(expected, tres);
+    }
+}
@@ -0,0 +1,199 @@
+use super::{encode, save, write_pages, Exporter, Palette};
+use crate::internal_sheet::{Frame, SpriteSheet};
+use crate::Cfg;
+use benchy::Benchy;
+use image::imageops;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::prelude::*;
+
+/// libGDX's TextureAtlas text format, with a section for each page.
+/// Each animation direction is a region name, with a region for each of its frames at its index in the animation.
+/// Only the color pages are exported.
+pub struct LibGdx;
+
+impl Exporter for LibGdx {
+    fn export(
+        &self,
+        path: &str,
+        file_name: &str,
+        sprite_sheet: &SpriteSheet,
+        cfg: &Cfg,
+        palette: Option<&Palette>,
+    ) {
+        Benchy::time("export_libgdx");
+
+        let has_rotated = sprite_sheet
+            .animations
+            .iter()
+            .flat_map(|a| a.directional_animations.iter())
+            .flat_map(|d| d.frames.iter())
+            .any(|f| f.rotated);
+
+        let images = if has_rotated {
+            write_counter_clockwise_pages(path, file_name, sprite_sheet, cfg, palette)
+        } else {
+            write_pages(path, file_name, &sprite_sheet.pages)
+        };
+
+        let mut atlas = String::new();
+
+        for (page_idx, page) in sprite_sheet.pages.iter().enumerate() {
+            // Each page starts after a blank line
+            atlas.push_str(&format!(
+                "\n{}\nsize: {},{}\nformat: RGBA8888\nfilter: Nearest,Nearest\nrepeat: none\n",
+                images[page_idx], page.width_px, page.height_px
+            ));
+
+            for animation in sprite_sheet.animations.iter() {
+                for direction in animation.directional_animations.iter() {
+                    let name = format!("{}_{}", animation.name, direction.direction);
+
+                    for (frame_idx, frame) in direction.frames.iter().enumerate() {
+                        if frame.page as usize == page_idx {
+                            atlas.push_str(&region(&name, frame_idx, frame));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut file = File::create(format!("{}{}.atlas", path, file_name)).unwrap();
+        file.write_all(atlas.as_bytes()).unwrap();
+    }
+}
+
+/// Writes a frame's region.
+/// The size is the unrotated size, and libGDX measures the offset from the bottom left of the original image.
+fn region<'a>(name: &'a str, index: usize, frame: &Frame) -> String {
+    let offset_y = frame.source_h_px - frame.offset_y_px - frame.height_px;
+
+    format!(
+        "{}\n  rotate: {}\n  xy: {}, {}\n  size: {}, {}\n  orig: {}, {}\n  offset: {}, {}\n  index: {}\n",
+        name,
+        frame.rotated,
+        frame.start_x_px,
+        frame.start_y_px,
+        frame.width_px,
+        frame.height_px,
+        frame.source_w_px,
+        frame.source_h_px,
+        frame.offset_x_px,
+        offset_y,
+        index
+    )
+}
+
+/// libGDX stores rotated regions 90 degrees counter clockwise, while the sheet's are clockwise.
+/// Writes copies of the pages with each rotated region turned around, returning their file names.
+fn write_counter_clockwise_pages<'a>(
+    path: &'a str,
+    file_name: &'a str,
+    sprite_sheet: &SpriteSheet,
+    cfg: &Cfg,
+    palette: Option<&Palette>,
+) -> Vec<String> {
+    let num_pages = sprite_sheet.pages.len();
+
+    sprite_sheet
+        .pages
+        .iter()
+        .enumerate()
+        .map(|(page_idx, page)| {
+            let mut image = page.image.clone();
+
+            let rotated_frames = sprite_sheet
+                .animations
+                .iter()
+                .flat_map(|a| a.directional_animations.iter())
+                .flat_map(|d| d.frames.iter())
+                .filter(|f| f.rotated && f.page as usize == page_idx);
+
+            // Deduped frames share a region, which must only be turned once
+            let mut turned_regions = HashSet::new();
+
+            for frame in rotated_frames {
+                if !turned_regions.insert((frame.start_x_px, frame.start_y_px)) {
+                    continue;
+                }
+
+                // Rotated frames take up height x width on the page, and the extrusion is turned along with them
+                let extrude_px = cfg.extrude_px;
+                let (x, y) = (frame.start_x_px - extrude_px, frame.start_y_px - extrude_px);
+                let turned = imageops::rotate180(&imageops::crop_imm(
+                    &image,
+                    x,
+                    y,
+                    frame.height_px + extrude_px * 2,
+                    frame.width_px + extrude_px * 2,
+                ));
+                imageops::replace(&mut image, &turned, x, y);
+            }
+
+            let name = format!(
+                "{}.png",
+                save::page_file_name(file_name, "_libgdx", page_idx, num_pages)
+            );
+
+            let mut file = File::create(format!("{}{}", path, name)).unwrap();
+            file.write_all(&encode::page_png(&image, palette)).unwrap();
+
+            name
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_sheet;
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn exports_atlas() {
+        let sprite_sheet = test_sheet::sheet();
+        let path = test_sheet::export(&LibGdx, &sprite_sheet, "libgdx");
+        let atlas = fs::read_to_string(path.join("test.atlas")).unwrap();
+        let page = image::open(path.join("test_libgdx.png"))
+            .unwrap()
+            .into_rgba8();
+        fs::remove_dir_all(&path).unwrap();
+
+        // Offsets are from the bottom of the source, and sizes are unrotated
+        let expected = "
+test_libgdx.png
+size: 16,16
+format: RGBA8888
+filter: Nearest,Nearest
+repeat: none
+walk_0
+  rotate: false
+  xy: 1, 1
+  size: 5, 4
+  orig: 12, 10
+  offset: 2, 3
+  index: 0
+walk_0
+  rotate: true
+  xy: 8, 1
+  size: 6, 3
+  orig: 6, 3
+  offset: 0, 0
+  index: 1
+";
+        assert_eq!(expected, atlas);
+
+        // The rotated frame's 3 x 6 region is turned around, and the rest of the page is left as is
+        let original = &sprite_sheet.pages[0].image;
+        for (x, y, p) in page.enumerate_pixels() {
+            let in_region = (8..11).contains(&x) && (1..7).contains(&y);
+            let expected = if in_region {
+                original.get_pixel(8 + 10 - x, 1 + 6 - y)
+            } else {
+                original.get_pixel(x, y)
+            };
+
+            assert_eq!(expected, p, "({}, {})", x, y);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+mod aseprite;
+mod godot;
+mod libgdx;
+mod texture_packer;
+
+use super::{encode, save, Palette};
+use crate::cfg::ExporterKind;
+use crate::internal_sheet::{Page, SpriteSheet};
+use crate::Cfg;
+use std::fs::File;
+use std::io::prelude::*;
+
+/// A format the sheet's metadata can be exported as.
+pub trait Exporter {
+    /// Writes the sheet's files to the output path, named after the given file name.
+    /// The palette is the one the pages were encoded with, if any.
+    fn export(
+        &self,
+        path: &str,
+        file_name: &str,
+        sprite_sheet: &SpriteSheet,
+        cfg: &Cfg,
+        palette: Option<&Palette>,
+    );
+}
+
+/// Returns the exporter for the given kind.
+pub fn exporter(kind: ExporterKind) -> Box<dyn Exporter> {
+    match kind {
+        ExporterKind::Builder => Box::new(Builder),
+        ExporterKind::TexturePackerHash => Box::new(texture_packer::TexturePacker { hash: true }),
+        ExporterKind::TexturePackerArray => Box::new(texture_packer::TexturePacker { hash: false }),
+        ExporterKind::Aseprite => Box::new(aseprite::Aseprite),
+        ExporterKind::Godot => Box::new(godot::Godot),
+        ExporterKind::LibGdx => Box::new(libgdx::LibGdx),
+    }
+}
+
+/// The builder sheet JSON, which can embed its images.
+struct Builder;
+
+impl Exporter for Builder {
+    fn export(
+        &self,
+        path: &str,
+        file_name: &str,
+        sprite_sheet: &SpriteSheet,
+        cfg: &Cfg,
+        _palette: Option<&Palette>,
+    ) {
+        save::execute(path, file_name, sprite_sheet, cfg.image_output);
+    }
+}
+
+/// Writes each page's PNG next to the exported files, for formats that refer to their images by file name.
+/// Returns the file name of each page, relative to the output path.
+fn write_pages<'a>(path: &'a str, file_name: &'a str, pages: &[Page]) -> Vec<String> {
+    pages
+        .iter()
+        .enumerate()
+        .map(|(page_idx, page)| {
+            let name = format!(
+                "{}.png",
+                save::page_file_name(file_name, "", page_idx, pages.len())
+            );
+
+            let mut file = File::create(format!("{}{}", path, name)).unwrap();
+            file.write_all(&page.image_png_bytes).unwrap();
+
+            name
+        })
+        .collect()
+}
+
+/// A small sheet for testing exporters, with a trimmed frame followed by a rotated one.
+#[cfg(test)]
+mod test_sheet {
+    use super::*;
+    use crate::internal_sheet::{Animation, DirectionalAnimation, Frame};
+    use image::{Rgba, RgbaImage};
+    use std::path::PathBuf;
+
+    pub fn sheet() -> SpriteSheet {
+        let trimmed = Frame {
+            duration_ms: 100,
+            empty: false,
+            grid_cell: None,
+            height_px: 4,
+            offset_x_px: 2,
+            offset_y_px: 3,
+            page: 0,
+            pivot_px: Some((6.0, 7.5)),
+            rotated: false,
+            source_w_px: 12,
+            source_h_px: 10,
+            start_x_px: 1,
+            start_y_px: 1,
+            width_px: 5,
+        };
+
+        // Takes up 3 x 6 on the page
+        let rotated = Frame {
+            duration_ms: 150,
+            empty: false,
+            grid_cell: None,
+            height_px: 3,
+            offset_x_px: 0,
+            offset_y_px: 0,
+            page: 0,
+            pivot_px: None,
+            rotated: true,
+            source_w_px: 6,
+            source_h_px: 3,
+            start_x_px: 8,
+            start_y_px: 1,
+            width_px: 6,
+        };
+
+        let animation = Animation {
+            directional_animations: vec![DirectionalAnimation {
+                direction: 0,
+                frames: vec![trimmed, rotated],
+                page: 0,
+                start_x_px: 1,
+                start_y_px: 1,
+            }],
+            name: "walk".to_string(),
+            fps: 10.0,
+            frame_step: 1,
+            looping: false,
+            page: 0,
+            start_x_px: 1,
+            start_y_px: 1,
+        };
+
+        let image = RgbaImage::from_fn(16, 16, |x, y| Rgba([x as u8 * 16, y as u8 * 16, 0, 255]));
+        let page = Page {
+            width_px: image.width(),
+            height_px: image.height(),
+            image_png_bytes: encode::rgba_png(&image),
+            image,
+            texture_bytes: vec![],
+        };
+
+        SpriteSheet::new(vec![page], vec![animation])
+    }
+
+    /// Exports the sheet as "test" to a new folder, returning the folder.
+    pub fn export(exporter: &dyn Exporter, sprite_sheet: &SpriteSheet, folder: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}_{}", folder, std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+
+        let path_str = format!("{}/", path.to_str().unwrap());
+        exporter.export(&path_str, "test", sprite_sheet, &Cfg::new(12, 10, 1), None);
+
+        path
+    }
+}
@@ -0,0 +1,231 @@
+use super::{save, write_pages, Exporter, Palette};
+use crate::internal_sheet::{Frame, SpriteSheet};
+use crate::Cfg;
+use benchy::Benchy;
+use serde_json::{json, Map, Value};
+use std::fs::File;
+use std::io::prelude::*;
+
+/// TexturePacker's JSON, as read by Phaser, PixiJS, Cocos and the like.
+/// Each page gets its own JSON, with multiple pages listing each other as related multi packs.
+/// Only the color pages are exported.
+pub struct TexturePacker {
+    /// Whether frames are keyed by name, rather than listed in order.
+    pub hash: bool,
+}
+
+impl Exporter for TexturePacker {
+    fn export(
+        &self,
+        path: &str,
+        file_name: &str,
+        sprite_sheet: &SpriteSheet,
+        _cfg: &Cfg,
+        _palette: Option<&Palette>,
+    ) {
+        Benchy::time("export_texture_packer");
+
+        let images = write_pages(path, file_name, &sprite_sheet.pages);
+
+        let kind = if self.hash { "hash" } else { "array" };
+        let json_names: Vec<String> = (0..sprite_sheet.pages.len())
+            .map(|page_idx| {
+                let name = format!("{}_texturepacker_{}", file_name, kind);
+                let name = save::page_file_name(&name, "", page_idx, sprite_sheet.pages.len());
+
+                format!("{}.json", name)
+            })
+            .collect();
+
+        for (page_idx, page) in sprite_sheet.pages.iter().enumerate() {
+            let mut hash_frames = Map::new();
+            let mut array_frames = vec![];
+            let mut animations = Map::new();
+
+            for animation in sprite_sheet.animations.iter() {
+                for direction in animation.directional_animations.iter() {
+                    let mut names = vec![];
+
+                    for (frame_idx, frame) in direction.frames.iter().enumerate() {
+                        if frame.page as usize != page_idx {
+                            continue;
+                        }
+
+                        let name =
+                            format!("{}/{}/{}", animation.name, direction.direction, frame_idx);
+                        let mut frame_json = frame_json(frame);
+
+                        if self.hash {
+                            hash_frames.insert(name.clone(), frame_json);
+                        } else {
+                            frame_json["filename"] = name.clone().into();
+                            array_frames.push(frame_json);
+                        }
+
+                        names.push(name);
+                    }
+
+                    if !names.is_empty() {
+                        let name = format!("{}/{}", animation.name, direction.direction);
+                        animations.insert(name, names.into());
+                    }
+                }
+            }
+
+            let frames = if self.hash {
+                Value::Object(hash_frames)
+            } else {
+                Value::Array(array_frames)
+            };
+
+            let mut json = json!({
+                "frames": frames,
+                "animations": animations,
+                "meta": {
+                    "app": env!("CARGO_PKG_NAME"),
+                    "version": "1.0",
+                    "image": images[page_idx],
+                    "format": "RGBA8888",
+                    "size": { "w": page.width_px, "h": page.height_px },
+                    "scale": "1",
+                },
+            });
+
+            if json_names.len() > 1 {
+                json["meta"]["related_multi_packs"] = json_names
+                    .iter()
+                    .enumerate()
+                    .filter(|(idx, _)| *idx != page_idx)
+                    .map(|(_, name)| name.clone())
+                    .collect::<Vec<String>>()
+                    .into();
+            }
+
+            let mut file = File::create(format!("{}{}", path, json_names[page_idx])).unwrap();
+            file.write_all(serde_json::to_string(&json).unwrap().as_bytes())
+                .unwrap();
+        }
+    }
+}
+
+/// Maps a frame to TexturePacker's fields.
+/// Rotated frames are stored 90 degrees clockwise like TexturePacker's, with the frame size being the unrotated size.
+/// Empty frames keep their place in the animation with a zero sized frame.
+pub fn frame_json(frame: &Frame) -> Value {
+    let trimmed = frame.offset_x_px != 0
+        || frame.offset_y_px != 0
+        || frame.width_px != frame.source_w_px
+        || frame.height_px != frame.source_h_px;
+
+    let mut json = json!({
+        "frame": {
+            "x": frame.start_x_px,
+            "y": frame.start_y_px,
+            "w": frame.width_px,
+            "h": frame.height_px,
+        },
+        "rotated": frame.rotated,
+        "trimmed": trimmed,
+        "spriteSourceSize": {
+            "x": frame.offset_x_px,
+            "y": frame.offset_y_px,
+            "w": frame.width_px,
+            "h": frame.height_px,
+        },
+        "sourceSize": {
+            "w": frame.source_w_px,
+            "h": frame.source_h_px,
+        },
+    });
+
+    // Pivots are normalized to the source size
+    if let Some((x, y)) = frame.pivot_px {
+        json["pivot"] = json!({
+            "x": x / frame.source_w_px as f32,
+            "y": y / frame.source_h_px as f32,
+        });
+    }
+
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_sheet;
+    use super::*;
+    use std::fs;
+
+    fn frames() -> (Value, Value) {
+        let trimmed = json!({
+            "frame": { "x": 1, "y": 1, "w": 5, "h": 4 },
+            "rotated": false,
+            "trimmed": true,
+            "spriteSourceSize": { "x": 2, "y": 3, "w": 5, "h": 4 },
+            "sourceSize": { "w": 12, "h": 10 },
+            "pivot": { "x": 0.5, "y": 0.75 },
+        });
+        let rotated = json!({
+            "frame": { "x": 8, "y": 1, "w": 6, "h": 3 },
+            "rotated": true,
+            "trimmed": false,
+            "spriteSourceSize": { "x": 0, "y": 0, "w": 6, "h": 3 },
+            "sourceSize": { "w": 6, "h": 3 },
+        });
+
+        (trimmed, rotated)
+    }
+
+    fn meta() -> Value {
+        json!({
+            "app": env!("CARGO_PKG_NAME"),
+            "version": "1.0",
+            "image": "test.png",
+            "format": "RGBA8888",
+            "size": { "w": 16, "h": 16 },
+            "scale": "1",
+        })
+    }
+
+    #[test]
+    fn exports_hash() {
+        let path = test_sheet::export(
+            &TexturePacker { hash: true },
+            &test_sheet::sheet(),
+            "texture_packer_hash",
+        );
+        let json = fs::read_to_string(path.join("test_texturepacker_hash.json")).unwrap();
+        assert!(path.join("test.png").exists());
+        fs::remove_dir_all(&path).unwrap();
+
+        let (trimmed, rotated) = frames();
+        let expected = json!({
+            "frames": { "walk/0/0": trimmed, "walk/0/1": rotated },
+            "animations": { "walk/0": ["walk/0/0", "walk/0/1"] },
+            "meta": meta(),
+        });
+
+        assert_eq!(expected, serde_json::from_str::<Value>(&json).unwrap());
+    }
+
+    #[test]
+    fn exports_array() {
+        let path = test_sheet::export(
+            &TexturePacker { hash: false },
+            &test_sheet::sheet(),
+            "texture_packer_array",
+        );
+        let json = fs::read_to_string(path.join("test_texturepacker_array.json")).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+
+        let (mut trimmed, mut rotated) = frames();
+        trimmed["filename"] = "walk/0/0".into();
+        rotated["filename"] = "walk/0/1".into();
+        let expected = json!({
+            "frames": [trimmed, rotated],
+            "animations": { "walk/0": ["walk/0/0", "walk/0/1"] },
+            "meta": meta(),
+        });
+
+        assert_eq!(expected, serde_json::from_str::<Value>(&json).unwrap());
+    }
+}
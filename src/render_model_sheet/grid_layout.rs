@@ -0,0 +1,121 @@
+use super::layout::{Layout, Placement, Spacing};
+use crate::cfg::{GridAnchor, GridCellSize};
+use crate::internal_sheet::{Frame, Grid, GridCell};
+use crate::Cfg;
+use image::{DynamicImage, GenericImageView};
+
+/// Places every frame in a uniform cell. Each direction starts on a new row.
+pub fn execute(directions: &[Vec<(&Frame, &DynamicImage)>], cfg: &Cfg, spacing: Spacing) -> Layout {
+    let (cell_w, cell_h) = match cfg.grid_cell {
+        GridCellSize::Max => {
+            let mut w = 1;
+            let mut h = 1;
+            for (_, img) in directions.iter().flatten() {
+                w = img.width().max(w);
+                h = img.height().max(h);
+            }
+
+            (w, h)
+        }
+        GridCellSize::Render => {
+            // Renders padded for an outline are larger than the sprite size
+            let mut w = 1;
+            let mut h = 1;
+            for (frame, _) in directions.iter().flatten() {
+                w = frame.source_w_px.max(w);
+                h = frame.source_h_px.max(h);
+            }
+
+            (w, h)
+        }
+    };
+
+    let (slot_w, slot_h) = spacing.slot_size(cell_w, cell_h);
+
+    // Wrap rows that are too long, either from the config or the page size
+    let most_frames = directions.iter().map(|d| d.len()).max().unwrap_or_default() as u32;
+    let mut columns = if cfg.grid_columns > 0 {
+        cfg.grid_columns
+    } else {
+        most_frames.max(1)
+    };
+    let mut rows_per_page = u32::MAX;
+
+    if let Some(max_bin_size) = spacing.max_bin_size(cfg.max_texture_size) {
+        if max_bin_size < slot_w || max_bin_size < slot_h {
+            panic!(
+                "A {}x{} px grid cell doesn't fit in the max texture size of {} px.",
+                cell_w,
+                cell_h,
+                cfg.max_texture_size.unwrap_or_default()
+            );
+        }
+
+        columns = columns.min(max_bin_size / slot_w);
+        rows_per_page = max_bin_size / slot_h;
+    }
+
+    let mut placements = vec![];
+    let mut rows = 0;
+    let mut page_sizes: Vec<(u32, u32)> = vec![];
+
+    for direction in directions.iter() {
+        for (idx, (frame, img)) in direction.iter().enumerate() {
+            let column = idx as u32 % columns;
+            let row = rows + idx as u32 / columns;
+            let page = (row / rows_per_page) as usize;
+            let page_row = row % rows_per_page;
+
+            // Anchor the frame in its cell
+            let (anchor_x, anchor_y) = match cfg.grid_cell {
+                GridCellSize::Render => (frame.offset_x_px, frame.offset_y_px),
+                GridCellSize::Max => {
+                    let free_w = cell_w - img.width();
+                    let free_h = cell_h - img.height();
+
+                    match cfg.grid_anchor {
+                        GridAnchor::TopLeft => (0, 0),
+                        GridAnchor::Center => (free_w / 2, free_h / 2),
+                        GridAnchor::Bottom => (free_w / 2, free_h),
+                    }
+                }
+            };
+
+            let slot_x = column * slot_w;
+            let slot_y = page_row * slot_h;
+            let (x, y) = spacing.frame_start(slot_x + anchor_x, slot_y + anchor_y);
+
+            // Grow the page to hold the cell
+            while page_sizes.len() <= page {
+                page_sizes.push((0, 0));
+            }
+            let (used_w, used_h) = &mut page_sizes[page];
+            *used_w = (*used_w).max(slot_x + slot_w);
+            *used_h = (*used_h).max(slot_y + slot_h);
+
+            placements.push(Placement {
+                page,
+                x,
+                y,
+                rotated: false,
+                grid_cell: Some(GridCell { column, row }),
+            });
+        }
+
+        rows += (direction.len() as u32).div_ceil(columns);
+    }
+
+    Layout {
+        page_sizes: page_sizes
+            .iter()
+            .map(|(w, h)| spacing.page_size(*w, *h))
+            .collect(),
+        placements,
+        grid: Some(Grid {
+            cell_w_px: cell_w,
+            cell_h_px: cell_h,
+            columns,
+            rows,
+        }),
+    }
+}
@@ -0,0 +1,77 @@
+use crate::internal_sheet::{Grid, GridCell};
+use crate::Cfg;
+
+/// Where a frame's pixels go in the sheet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Placement {
+    pub page: usize,
+    pub x: u32,
+    pub y: u32,
+    pub rotated: bool,
+    pub grid_cell: Option<GridCell>,
+}
+
+/// Frames laid out across one or more pages.
+pub struct Layout {
+    pub page_sizes: Vec<(u32, u32)>,
+    /// The placement for each frame, in animation, direction then frame order.
+    pub placements: Vec<Placement>,
+    pub grid: Option<Grid>,
+}
+
+/// The space around each frame and the edges of the sheet.
+/// Each frame is surrounded by its extruded edges, with padding to the right and below.
+/// The trailing padding may hang over the sheet border, since it's transparent either way.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Spacing {
+    pub border_px: u32,
+    pub extrude_px: u32,
+    pub padding_px: u32,
+}
+
+impl Spacing {
+    pub fn new(cfg: &Cfg) -> Self {
+        Self {
+            border_px: cfg.border_px,
+            extrude_px: cfg.extrude_px,
+            padding_px: cfg.padding_px,
+        }
+    }
+
+    /// Returns the space a frame of the given size takes up when laid out.
+    pub fn slot_size(&self, w: u32, h: u32) -> (u32, u32) {
+        let spacing_px = 2 * self.extrude_px + self.padding_px;
+
+        (w + spacing_px, h + spacing_px)
+    }
+
+    /// Returns the largest area slots can be laid out in for the given max texture size.
+    pub fn max_bin_size(&self, max_texture_size: Option<u32>) -> Option<u32> {
+        max_texture_size.map(|max_texture_size| {
+            if max_texture_size <= 2 * self.border_px {
+                panic!(
+                    "A border of {} px leaves no room in the max texture size of {} px.",
+                    self.border_px, max_texture_size
+                );
+            }
+
+            max_texture_size - 2 * self.border_px + self.padding_px
+        })
+    }
+
+    /// Returns the page size needed to hold slots covering the given area.
+    pub fn page_size(&self, used_w: u32, used_h: u32) -> (u32, u32) {
+        (
+            used_w - self.padding_px + 2 * self.border_px,
+            used_h - self.padding_px + 2 * self.border_px,
+        )
+    }
+
+    /// Returns where the frame's pixels start for a slot at the given position.
+    pub fn frame_start(&self, slot_x: u32, slot_y: u32) -> (u32, u32) {
+        (
+            slot_x + self.border_px + self.extrude_px,
+            slot_y + self.border_px + self.extrude_px,
+        )
+    }
+}
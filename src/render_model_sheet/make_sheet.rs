@@ -1,79 +1,442 @@
+use super::layout::{Placement, Spacing};
+use super::{alpha, grid_layout, packed_layout, team_mask};
+use crate::cfg::{LayoutKind, PassKind, TextureFormat};
+use crate::internal_sheet::TeamMaskSource;
 use crate::*;
 use benchy::Benchy;
-use image::{DynamicImage, GenericImage, GenericImageView};
+use image::{DynamicImage, GenericImage, GenericImageView, RgbaImage};
 
 /// Converts all animations into a sprite sheet
-pub fn execute(mut animations: Vec<Render>) -> (internal_sheet::SpriteSheet, Vec<u8>) {
+pub fn execute(renders: Vec<Render>, cfg: &Cfg) -> internal_sheet::SpriteSheet {
     Benchy::time("make_sheet");
 
-    // Split out animations and pngs
+    // Split out animations and frame images
+    let (mut animations, frame_imgs) = {
+        let mut a = vec![];
+        let mut f = vec![];
 
-    let offset_x = 0;
-    let mut offset_y = 0;
+        for (animation, imgs) in renders {
+            a.push(animation);
+            f.push(imgs);
+        }
 
-    let (animations, pngs) = {
-        let mut a = vec![];
-        let mut p = vec![];
+        (a, f)
+    };
 
-        while animations.is_empty() == false {
-            let (mut animation, png) = animations.remove(0);
+    let spacing = Spacing::new(cfg);
 
-            // Note the offset for the animation
-            animation.start_x_px = offset_x;
-            animation.start_y_px = offset_y;
+    // Ensure every frame can fit on a page
+    if let Some(max_texture_size) = cfg.max_texture_size {
+        for (animation, directions) in animations.iter().zip(frame_imgs.iter()) {
+            for (direction, imgs) in animation.directional_animations.iter().zip(directions) {
+                for (frame_idx, img) in imgs.iter().map(|i| &i.color).enumerate() {
+                    let w = img.width() + 2 * spacing.extrude_px + 2 * spacing.border_px;
+                    let h = img.height() + 2 * spacing.extrude_px + 2 * spacing.border_px;
 
-            // Update offsets for next image; only doing vertical since everything gets stacked vertically
-            offset_y += png.height();
+                    if w > max_texture_size || h > max_texture_size {
+                        panic!(
+                            "Frame {} of animation '{}' direction {} needs {}x{} px with its extrusion and the sheet border, which doesn't fit in the max texture size of {} px.",
+                            frame_idx,
+                            animation.name,
+                            direction.direction,
+                            w,
+                            h,
+                            max_texture_size
+                        );
+                    }
+                }
+            }
+        }
+    }
 
-            a.push(animation);
-            p.push(png);
+    // Lay out the frames
+    let mut layout = match cfg.layout {
+        LayoutKind::Packed => {
+            let imgs: Vec<&FrameImgs> = frame_imgs.iter().flatten().flatten().collect();
+
+            packed_layout::execute(&imgs, cfg, spacing)
         }
+        LayoutKind::Grid => {
+            let mut directions = vec![];
+            for (animation, imgs) in animations.iter().zip(frame_imgs.iter()) {
+                for (direction, imgs) in animation.directional_animations.iter().zip(imgs) {
+                    directions.push(
+                        direction
+                            .frames
+                            .iter()
+                            .zip(imgs.iter().map(|i| &i.color))
+                            .collect(),
+                    );
+                }
+            }
 
-        (a, p)
+            grid_layout::execute(&directions, cfg, spacing)
+        }
     };
 
-    let final_png = join_pngs_vertically(pngs).expect("Didn't get a sprite sheet!");
+    // Every frame may be empty, leaving nothing to pack
+    if layout.page_sizes.is_empty() {
+        if layout.placements.is_empty() {
+            panic!("Didn't get a sprite sheet!");
+        }
+
+        layout.page_sizes.push((1, 1));
+    }
+
+    // Copy each frame to its placement
+    let new_pages = || -> Vec<DynamicImage> {
+        layout
+            .page_sizes
+            .iter()
+            .map(|(w, h)| DynamicImage::new_rgba8(*w, *h))
+            .collect()
+    };
+
+    let mut page_pngs = new_pages();
+
+    // Extra passes get their own pages with the same layout, in the order they're first seen
+    let mut layer_pngs: Vec<(PassKind, Vec<DynamicImage>)> = vec![];
+
+    for (imgs, placement) in frame_imgs
+        .iter()
+        .flatten()
+        .flatten()
+        .zip(layout.placements.iter())
+    {
+        place(&mut page_pngs, &imgs.color, placement, spacing);
+
+        for (pass, img) in imgs.passes.iter() {
+            if !layer_pngs.iter().any(|(p, _)| p == pass) {
+                layer_pngs.push((*pass, new_pages()));
+            }
 
-    let width_px = final_png.width();
-    let height_px = final_png.height();
+            if let Some((_, pages)) = layer_pngs.iter_mut().find(|(p, _)| p == pass) {
+                place(pages, img, placement, spacing);
+            }
+        }
+    }
+
+    // Note the positions
+    let mut placements = layout.placements.iter();
+
+    for animation in animations.iter_mut() {
+        for direction in animation.directional_animations.iter_mut() {
+            for frame in direction.frames.iter_mut() {
+                let placement = placements.next().unwrap();
+
+                frame.page = placement.page as u32;
+                frame.rotated = placement.rotated;
+                frame.grid_cell = placement.grid_cell;
+                frame.start_x_px = placement.x;
+                frame.start_y_px = placement.y;
+            }
+
+            // Parents start at the top left most point of their non-empty children on the same page
+            let (page, x, y) = top_left(
+                direction
+                    .frames
+                    .iter()
+                    .filter(|f| !f.empty)
+                    .map(|f| (f.page, f.start_x_px, f.start_y_px)),
+            );
+            direction.page = page;
+            direction.start_x_px = x;
+            direction.start_y_px = y;
+        }
+
+        let (page, x, y) = top_left(
+            animation
+                .directional_animations
+                .iter()
+                .map(|d| (d.page, d.start_x_px, d.start_y_px)),
+        );
+        animation.page = page;
+        animation.start_x_px = x;
+        animation.start_y_px = y;
+    }
+
+    let mut sprite_sheet = internal_sheet::SpriteSheet::new(to_pages(page_pngs, cfg), animations);
+    sprite_sheet.layers = layer_pngs
+        .into_iter()
+        .map(|(pass, pngs)| internal_sheet::Layer {
+            name: pass.name().to_string(),
+            pages: to_pages(pngs, cfg),
+        })
+        .collect();
+    sprite_sheet.grid = layout.grid;
 
-    let mut image_png_bytes: Vec<u8> = Vec::new();
-    final_png
-        .write_to(&mut image_png_bytes, image::ImageOutputFormat::Png)
-        .unwrap();
+    // Describe the team color mask, if there is one
+    let has_team_mask = sprite_sheet
+        .layers
+        .iter()
+        .any(|l| l.name == PassKind::TeamMask.name());
+    if has_team_mask {
+        let source = if cfg.passes.contains(&PassKind::TeamMask) {
+            TeamMaskSource::Pass
+        } else {
+            TeamMaskSource::ColorKey
+        };
 
-    (
-        internal_sheet::SpriteSheet::new(image_png_bytes.clone(), width_px, height_px, animations),
-        image_png_bytes,
-    )
+        let lut = if cfg.team_colors.is_empty() {
+            None
+        } else {
+            let image = team_mask::lut(&cfg.team_colors, cfg.team_color_key);
+
+            Some(internal_sheet::Page {
+                width_px: image.width(),
+                height_px: image.height(),
+                image,
+                image_png_bytes: vec![],
+                texture_bytes: vec![],
+            })
+        };
+
+        sprite_sheet.team_mask = Some(internal_sheet::TeamMask {
+            layer: PassKind::TeamMask.name().to_string(),
+            source,
+            lut,
+            team_colors: cfg.team_colors.clone(),
+        });
+    }
+
+    sprite_sheet
 }
 
-fn join_pngs_vertically(pngs: Vec<DynamicImage>) -> Option<DynamicImage> {
-    // Source dimensions
-    let mut height = 0;
-    let mut width = 0;
+/// Copies the frame to its placement on the pages.
+fn place(pages: &mut [DynamicImage], img: &DynamicImage, placement: &Placement, spacing: Spacing) {
+    let rotated_img;
+    let img = if placement.rotated {
+        rotated_img = img.rotate90();
+        &rotated_img
+    } else {
+        img
+    };
 
-    for png in pngs.iter() {
-        height += png.height();
-        width = png.width().max(width);
+    let page = &mut pages[placement.page];
+    page.copy_from(img, placement.x, placement.y).unwrap();
+
+    if spacing.extrude_px > 0 {
+        extrude(page, img, placement.x, placement.y, spacing.extrude_px);
     }
+}
+
+/// Converts the page images to sheet pages. They get encoded once every sheet in the run is made.
+fn to_pages(page_pngs: Vec<DynamicImage>, cfg: &Cfg) -> Vec<internal_sheet::Page> {
+    page_pngs
+        .into_iter()
+        .map(|png| {
+            let mut image = png.into_rgba8();
+
+            // Block compression works on 4x4 blocks
+            if cfg.texture_format != TextureFormat::Png {
+                let w = image.width().div_ceil(4) * 4;
+                let h = image.height().div_ceil(4) * 4;
 
-    if height == 0 || width == 0 {
-        return None;
+                if (w, h) != image.dimensions() {
+                    let mut padded = RgbaImage::new(w, h);
+                    padded.copy_from(&image, 0, 0).unwrap();
+                    image = padded;
+                }
+            }
+
+            // Clean up the transparent pixels
+            if cfg.alpha_bleed {
+                alpha::bleed(&mut image);
+            }
+
+            internal_sheet::Page {
+                width_px: image.width(),
+                height_px: image.height(),
+                image,
+                image_png_bytes: vec![],
+                texture_bytes: vec![],
+            }
+        })
+        .collect()
+}
+
+/// Repeats the edge pixels of the image, which was copied to (x, y), outward by the given amount.
+fn extrude(page: &mut DynamicImage, img: &DynamicImage, x: u32, y: u32, extrude_px: u32) {
+    if img.width() == 0 || img.height() == 0 {
+        return;
     }
 
-    // Create new img
-    let mut img = DynamicImage::new_rgba8(width, height);
+    let extrude_px = extrude_px as i64;
+    let (w, h) = (img.width() as i64, img.height() as i64);
+
+    for dy in -extrude_px..h + extrude_px {
+        for dx in -extrude_px..w + extrude_px {
+            let is_inside = dx >= 0 && dx < w && dy >= 0 && dy < h;
+            if is_inside {
+                continue;
+            }
 
-    // Note the coordinates to copy to
-    let copy_x = 0;
-    let mut copy_y = 0;
-    for png in pngs.iter() {
-        img.copy_from(png, copy_x, copy_y).unwrap();
+            // Use the closest pixel on the edge
+            let src_x = dx.max(0).min(w - 1) as u32;
+            let src_y = dy.max(0).min(h - 1) as u32;
+            let dst_x = (x as i64 + dx) as u32;
+            let dst_y = (y as i64 + dy) as u32;
 
-        // Increment the height for the next img
-        copy_y += png.height();
+            page.put_pixel(dst_x, dst_y, img.get_pixel(src_x, src_y));
+        }
     }
+}
+
+/// Returns the page of the first (page, x, y) position, along with the top left most point on that page.
+fn top_left<I>(mut positions: I) -> (u32, u32, u32)
+where
+    I: Iterator<Item = (u32, u32, u32)>,
+{
+    let (page, mut x, mut y) = match positions.next() {
+        Some(first) => first,
+        None => return (0, 0, 0),
+    };
+
+    for (p, px, py) in positions {
+        if p == page {
+            x = x.min(px);
+            y = y.min(py);
+        }
+    }
+
+    (page, x, y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::PackerKind;
+    use crate::internal_sheet::{Animation, DirectionalAnimation, Frame};
+    use image::Rgba;
+
+    const W: u32 = 3;
+    const H: u32 = 2;
+
+    /// A frame where every pixel is different, so each extruded pixel shows which edge pixel it came from.
+    fn frame_img(idx: u32) -> RgbaImage {
+        RgbaImage::from_fn(W, H, |x, y| {
+            Rgba([x as u8 * 80, y as u8 * 120, idx as u8 * 100 + 50, 255])
+        })
+    }
+
+    fn render(num_frames: u32) -> Render {
+        let frame = Frame {
+            duration_ms: 100,
+            empty: false,
+            grid_cell: None,
+            height_px: H,
+            offset_x_px: 5,
+            offset_y_px: 6,
+            page: 0,
+            pivot_px: None,
+            rotated: false,
+            source_w_px: 16,
+            source_h_px: 16,
+            start_x_px: 0,
+            start_y_px: 0,
+            width_px: W,
+        };
 
-    Some(img)
+        let animation = Animation {
+            directional_animations: vec![DirectionalAnimation {
+                direction: 0,
+                frames: vec![frame; num_frames as usize],
+                page: 0,
+                start_x_px: 0,
+                start_y_px: 0,
+            }],
+            name: "idle".to_string(),
+            fps: 24.0,
+            frame_step: 1,
+            looping: true,
+            page: 0,
+            start_x_px: 0,
+            start_y_px: 0,
+        };
+
+        let imgs = (0..num_frames)
+            .map(|idx| FrameImgs {
+                color: DynamicImage::ImageRgba8(frame_img(idx)),
+                passes: vec![],
+            })
+            .collect();
+
+        (animation, vec![imgs])
+    }
+
+    #[test]
+    fn frames_are_extruded_and_spaced() {
+        let mut cfg = Cfg::new(16, 16, 1);
+        cfg.packer = PackerKind::Shelf;
+        cfg.extrude_px = 1;
+        cfg.padding_px = 2;
+        cfg.border_px = 3;
+        cfg.alpha_bleed = false;
+
+        let sheet = execute(vec![render(2)], &cfg);
+        let page = &sheet.pages[0].image;
+        let frames = &sheet.animations[0].directional_animations[0].frames;
+
+        let e = cfg.extrude_px as i64;
+        let mut extruded = vec![];
+
+        for (idx, frame) in frames.iter().enumerate() {
+            // The trimmed rect is kept, and the frame starts inside the border and its extrusion
+            assert_eq!(
+                (5, 6, W, H),
+                (
+                    frame.offset_x_px,
+                    frame.offset_y_px,
+                    frame.width_px,
+                    frame.height_px
+                )
+            );
+            assert!(frame.start_x_px >= cfg.border_px + cfg.extrude_px);
+            assert!(frame.start_y_px >= cfg.border_px + cfg.extrude_px);
+
+            let img = frame_img(idx as u32);
+            let (x, y) = (frame.start_x_px as i64, frame.start_y_px as i64);
+
+            // Each pixel of the extruded frame is the closest pixel of the frame
+            for dy in -e..H as i64 + e {
+                for dx in -e..W as i64 + e {
+                    let src_x = dx.max(0).min(W as i64 - 1) as u32;
+                    let src_y = dy.max(0).min(H as i64 - 1) as u32;
+
+                    assert_eq!(
+                        img.get_pixel(src_x, src_y),
+                        page.get_pixel((x + dx) as u32, (y + dy) as u32),
+                        "frame {} at ({}, {})",
+                        idx,
+                        dx,
+                        dy
+                    );
+                }
+            }
+
+            extruded.push((x - e, y - e, x + W as i64 + e, y + H as i64 + e));
+        }
+
+        // Extruded frames are at least the padding apart
+        let pad = cfg.padding_px as i64;
+        let (a, b) = (extruded[0], extruded[1]);
+        assert!(a.2 + pad <= b.0 || b.2 + pad <= a.0 || a.3 + pad <= b.1 || b.3 + pad <= a.1);
+
+        // The border is the same on every side, and everything else is transparent
+        let right = frames.iter().map(|f| f.start_x_px + W).max().unwrap();
+        let bottom = frames.iter().map(|f| f.start_y_px + H).max().unwrap();
+        assert_eq!(right + cfg.extrude_px + cfg.border_px, page.width());
+        assert_eq!(bottom + cfg.extrude_px + cfg.border_px, page.height());
+
+        for (x, y, p) in page.enumerate_pixels() {
+            let (x, y) = (x as i64, y as i64);
+            let is_extruded = extruded
+                .iter()
+                .any(|r| x >= r.0 && x < r.2 && y >= r.1 && y < r.3);
+
+            if !is_extruded {
+                assert_eq!(0, p[3], "({}, {})", x, y);
+            }
+        }
+    }
 }
@@ -1,27 +1,161 @@
+mod alpha;
+mod block_compress;
+mod crop;
+mod downsample;
+mod encode;
+mod exporters;
+mod grid_layout;
+mod layout;
 mod make_sheet;
+mod outline;
+mod packed_layout;
+mod packers;
+mod palette;
+mod quantize;
 mod render_animation;
 mod render_animations;
 mod save;
+mod shadow;
 mod sorted_map;
 mod source_imgs;
+mod team_mask;
+mod texture_container;
 
 use source_imgs::ImgToRender;
 
+use crate::cfg::ImageOutput;
+use crate::internal_sheet::SpriteSheet;
 use crate::Cfg;
 
-/// Renders the sprite sheet
-pub fn execute<'a>(input_path: &'a str, output_path: &'a str, model_name: &'a str, cfg: Cfg) {
-    let source_images = source_imgs::execute(input_path);
-    let rendered_animations = render_animations::execute(source_images);
-    let (spritesheet, image_png_bytes) = make_sheet::execute(rendered_animations);
+pub use palette::Palette;
 
-    // Debug final output
-    if cfg.output_render_pngs {
+/// A sprite sheet that's been made, but not yet encoded or saved.
+pub struct Sheet {
+    pub cfg: Cfg,
+    pub model_name: String,
+    pub sprite_sheet: SpriteSheet,
+}
+
+/// Makes the sprite sheet from the renders, followed by its drop shadow sheet if enabled.
+pub fn make<'a>(input_path: &'a str, model_name: &'a str, cfg: Cfg) -> Vec<Sheet> {
+    let source_images = source_imgs::execute(input_path, &cfg.passes);
+    let rendered_animations = render_animations::execute(source_images, &cfg);
+
+    // Shadows are cropped on their own, as they reach past their frames
+    let shadow_animations = if cfg.shadow {
+        Some(shadow::execute(&rendered_animations, &cfg))
+    } else {
+        None
+    };
+
+    let mut sprite_sheet = make_sheet::execute(rendered_animations, &cfg);
+
+    let mut sheets = vec![];
+    if let Some(shadow_animations) = shadow_animations {
+        let shadow_name = format!("{}_shadow", model_name);
+
+        let mut shadow_sheet = make_sheet::execute(shadow_animations, &cfg);
+        shadow_sheet.shadow_of = Some(model_name.to_string());
+        sprite_sheet.shadow_sheet = Some(shadow_name.clone());
+
+        sheets.push(Sheet {
+            cfg: cfg.clone(),
+            model_name: shadow_name,
+            sprite_sheet: shadow_sheet,
+        });
+    }
+
+    sheets.insert(
+        0,
+        Sheet {
+            cfg,
+            model_name: model_name.to_string(),
+            sprite_sheet,
+        },
+    );
+
+    sheets
+}
+
+/// Returns the palette every sheet in the run is restricted to, if any.
+/// Generated palettes cover the colors of all the sheets.
+pub fn palette(cfg: &Cfg, sheets: &[Sheet]) -> Option<Palette> {
+    if let Some(path) = &cfg.palette {
+        match Palette::load(path) {
+            Ok(palette) => return Some(palette),
+            Err(e) => panic!("Error loading palette: {}", e),
+        }
+    }
+
+    if cfg.palette_colors > 0 {
+        let imgs: Vec<_> = sheets
+            .iter()
+            .filter(|s| s.sprite_sheet.shadow_of.is_none())
+            .flat_map(|s| s.sprite_sheet.pages.iter())
+            .map(|p| &p.image)
+            .collect();
+
+        return Some(Palette::generate(&imgs, cfg.palette_colors));
+    }
+
+    None
+}
+
+/// Encodes and saves the sprite sheet.
+/// Shadow sheets are a single translucent color, so they're never restricted to the palette.
+pub fn save<'a>(output_path: &'a str, sheet: Sheet, palette: Option<&Palette>) {
+    let Sheet {
+        cfg,
+        model_name,
+        mut sprite_sheet,
+    } = sheet;
+
+    let palette = match sprite_sheet.shadow_of {
+        Some(_) => None,
+        None => palette,
+    };
+
+    encode::execute(&mut sprite_sheet, &cfg, palette);
+
+    // Debug final output, unless the images are already written next to the JSON
+    if cfg.output_render_pngs && cfg.image_output == ImageOutput::Embedded {
         use std::fs::File;
         use std::io::prelude::*;
-        let mut file = File::create(format!("{}{}.png", output_path, model_name)).unwrap();
-        file.write_all(&image_png_bytes).unwrap();
+
+        let mut sets = vec![(String::new(), &sprite_sheet.pages)];
+        for layer in sprite_sheet.layers.iter() {
+            sets.push((format!("_{}", layer.name), &layer.pages));
+        }
+
+        for (suffix, pages) in sets.iter() {
+            for (page_idx, page) in pages.iter().enumerate() {
+                let file_name = format!(
+                    "{}{}",
+                    output_path,
+                    save::page_file_name(&model_name, suffix, page_idx, pages.len())
+                );
+
+                let mut file = File::create(format!("{}.png", file_name)).unwrap();
+                file.write_all(&page.image_png_bytes).unwrap();
+
+                if let Some(texture) = sprite_sheet.texture {
+                    let file_name = format!("{}.{}", file_name, texture.format.name());
+
+                    let mut file = File::create(file_name).unwrap();
+                    file.write_all(&page.texture_bytes).unwrap();
+                }
+            }
+        }
+
+        if let Some(lut) = sprite_sheet.team_mask.as_ref().and_then(|t| t.lut.as_ref()) {
+            let file_name = format!("{}{}_team_lut.png", output_path, model_name);
+
+            let mut file = File::create(file_name).unwrap();
+            file.write_all(&lut.image_png_bytes).unwrap();
+        }
     }
 
-    save::execute(output_path, model_name, spritesheet);
+    for kind in cfg.exporters.iter() {
+        exporters::exporter(*kind).export(output_path, &model_name, &sprite_sheet, &cfg, palette);
+    }
 }
@@ -0,0 +1,63 @@
+use crate::cfg::{OutlineCorners, OutlineKind};
+use crate::Cfg;
+use image::{Rgba, RgbaImage};
+
+/// Draws an outline around the visible pixels of a render.
+/// This happens before cropping, so the crop grows to fit the outline.
+/// Returns the pixels that were drawn.
+pub fn execute(img: &mut RgbaImage, cfg: &Cfg) -> Vec<(u32, u32)> {
+    let (draw_outer, draw_inner) = match cfg.outline {
+        OutlineKind::None => return vec![],
+        OutlineKind::Outer => (true, false),
+        OutlineKind::Inner => (false, true),
+        OutlineKind::Both => (true, true),
+    };
+
+    let (w, h) = img.dimensions();
+    let thickness = cfg.outline_thickness as i64;
+    let visible: Vec<bool> = img.pixels().map(|p| p[3] > cfg.alpha_threshold).collect();
+    let is_visible = |x: i64, y: i64| visible[(y * w as i64 + x) as usize];
+
+    // Whether a pixel at the offset is close enough to be part of the outline
+    let is_within = |dx: i64, dy: i64| match cfg.outline_corners {
+        OutlineCorners::Square => dx.abs().max(dy.abs()) <= thickness,
+        OutlineCorners::Round => dx * dx + dy * dy <= thickness * thickness,
+    };
+
+    let mut outline = vec![];
+    for y in 0..h as i64 {
+        for x in 0..w as i64 {
+            let wants_outline = if is_visible(x, y) {
+                draw_inner
+            } else {
+                draw_outer
+            };
+            if !wants_outline {
+                continue;
+            }
+
+            // Outer outlines are transparent pixels near visible ones, and inner outlines are the reverse
+            let mut near_edge = false;
+            for dy in -thickness..=thickness {
+                for dx in -thickness..=thickness {
+                    let (nx, ny) = (x + dx, y + dy);
+                    let is_inside = nx >= 0 && ny >= 0 && nx < w as i64 && ny < h as i64;
+
+                    if is_inside && is_within(dx, dy) && is_visible(nx, ny) != is_visible(x, y) {
+                        near_edge = true;
+                    }
+                }
+            }
+
+            if near_edge {
+                outline.push((x as u32, y as u32));
+            }
+        }
+    }
+
+    for (x, y) in outline.iter() {
+        img.put_pixel(*x, *y, Rgba(cfg.outline_color));
+    }
+
+    outline
+}
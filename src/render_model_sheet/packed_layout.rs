@@ -0,0 +1,182 @@
+use super::layout::{Layout, Placement, Spacing};
+use super::packers;
+use crate::{Cfg, FrameImgs};
+use image::{DynamicImage, GenericImageView};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Packs every unique frame individually.
+pub fn execute(imgs: &[&FrameImgs], cfg: &Cfg, spacing: Spacing) -> Layout {
+    let (unique_imgs, unique_idxs) = if cfg.dedupe {
+        dedupe(imgs)
+    } else {
+        (imgs.to_vec(), (0..imgs.len()).collect())
+    };
+
+    // Empty frames don't take up any space
+    let mut packed_idxs = vec![];
+    let mut sizes = vec![];
+    for img in unique_imgs.iter().map(|i| &i.color) {
+        if img.width() == 0 || img.height() == 0 {
+            packed_idxs.push(None);
+        } else {
+            packed_idxs.push(Some(sizes.len()));
+            sizes.push(spacing.slot_size(img.width(), img.height()));
+        }
+    }
+
+    let packer = packers::packer(cfg.packer, cfg.allow_rotation);
+    let max_bin_size = spacing.max_bin_size(cfg.max_texture_size);
+    let packed = match packers::pack_pages(packer.as_ref(), &sizes, max_bin_size) {
+        Ok(packed) => packed,
+        Err(e) => panic!("Didn't get a sprite sheet! {}", e),
+    };
+
+    // Duplicates point at the shared region
+    let placements = unique_idxs
+        .iter()
+        .map(|idx| match packed_idxs[*idx] {
+            Some(packed_idx) => {
+                let (page, rect) = &packed.placements[packed_idx];
+                let (x, y) = spacing.frame_start(rect.x, rect.y);
+
+                Placement {
+                    page: *page,
+                    x,
+                    y,
+                    rotated: rect.rotated,
+                    grid_cell: None,
+                }
+            }
+            None => Placement {
+                page: 0,
+                x: 0,
+                y: 0,
+                rotated: false,
+                grid_cell: None,
+            },
+        })
+        .collect();
+
+    Layout {
+        page_sizes: packed
+            .page_sizes
+            .iter()
+            .map(|(w, h)| spacing.page_size(*w, *h))
+            .collect(),
+        placements,
+        grid: None,
+    }
+}
+
+/// Returns the pixel-unique images, along with the index of the unique image for every input image.
+/// Frames are only the same if all of their passes are.
+fn dedupe<'a>(imgs: &[&'a FrameImgs]) -> (Vec<&'a FrameImgs>, Vec<usize>) {
+    let mut unique_imgs: Vec<&FrameImgs> = vec![];
+    let mut unique_idxs = vec![];
+    let mut hashes: HashMap<u64, Vec<usize>> = HashMap::new();
+
+    for img in imgs.iter() {
+        let hash = {
+            let mut hasher = DefaultHasher::new();
+            for pass in passes(img) {
+                pass.dimensions().hash(&mut hasher);
+                pass.as_bytes().hash(&mut hasher);
+            }
+            hasher.finish()
+        };
+
+        // Compare the pixels in case of a collision
+        let candidates = hashes.entry(hash).or_default();
+        let existing = candidates.iter().find(|idx| {
+            let other = unique_imgs[**idx];
+            other.passes.len() == img.passes.len()
+                && other
+                    .passes
+                    .iter()
+                    .zip(img.passes.iter())
+                    .all(|(a, b)| a.0 == b.0)
+                && passes(other).zip(passes(img)).all(|(a, b)| {
+                    a.dimensions() == b.dimensions()
+                        && a.color() == b.color()
+                        && a.as_bytes() == b.as_bytes()
+                })
+        });
+
+        match existing {
+            Some(idx) => unique_idxs.push(*idx),
+            None => {
+                candidates.push(unique_imgs.len());
+                unique_idxs.push(unique_imgs.len());
+                unique_imgs.push(*img);
+            }
+        }
+    }
+
+    (unique_imgs, unique_idxs)
+}
+
+/// Returns every image of the frame.
+fn passes<'a>(imgs: &'a FrameImgs) -> impl Iterator<Item = &'a DynamicImage> {
+    std::iter::once(&imgs.color).chain(imgs.passes.iter().map(|(_, img)| img))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cfg::PassKind;
+    use image::{Rgba, RgbaImage};
+
+    fn frame(color: [u8; 4]) -> FrameImgs {
+        FrameImgs {
+            color: DynamicImage::ImageRgba8(RgbaImage::from_pixel(4, 3, Rgba(color))),
+            passes: vec![],
+        }
+    }
+
+    fn layout(imgs: &[FrameImgs]) -> Layout {
+        let imgs: Vec<&FrameImgs> = imgs.iter().collect();
+        let cfg = Cfg::new(64, 64, 1);
+
+        execute(&imgs, &cfg, Spacing::new(&cfg))
+    }
+
+    #[test]
+    fn identical_frames_share_a_region() {
+        let layout = layout(&[frame([10, 20, 30, 255]), frame([10, 20, 30, 255])]);
+
+        assert_eq!(layout.placements[0], layout.placements[1]);
+    }
+
+    #[test]
+    fn frames_differing_by_a_pixel_get_their_own_region() {
+        let mut different = frame([10, 20, 30, 255]);
+        if let DynamicImage::ImageRgba8(img) = &mut different.color {
+            img.put_pixel(3, 2, Rgba([10, 20, 31, 255]));
+        }
+
+        let layout = layout(&[frame([10, 20, 30, 255]), different]);
+
+        assert_ne!(
+            (layout.placements[0].x, layout.placements[0].y),
+            (layout.placements[1].x, layout.placements[1].y)
+        );
+    }
+
+    #[test]
+    fn frames_differing_in_a_pass_get_their_own_region() {
+        let with_pass = |value| {
+            let mut imgs = frame([10, 20, 30, 255]);
+            let pass = RgbaImage::from_pixel(4, 3, Rgba([value, 0, 0, 255]));
+            imgs.passes
+                .push((PassKind::Depth, DynamicImage::ImageRgba8(pass)));
+            imgs
+        };
+
+        let layout = layout(&[with_pass(1), with_pass(1), with_pass(2)]);
+
+        assert_eq!(layout.placements[0], layout.placements[1]);
+        assert_ne!(layout.placements[0], layout.placements[2]);
+    }
+}
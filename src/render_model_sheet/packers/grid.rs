@@ -0,0 +1,41 @@
+//! Grid packing; every frame gets a cell the size of the largest frame.
+use super::{Packer, Rect};
+
+/// Places frames in uniform cells, in the order they were given.
+pub struct Grid;
+
+impl Packer for Grid {
+    fn pack(&self, sizes: &[(u32, u32)], bin_w: u32, bin_h: u32) -> Vec<Option<Rect>> {
+        let mut cell_w = 0;
+        let mut cell_h = 0;
+        for (w, h) in sizes.iter() {
+            cell_w = cell_w.max(*w);
+            cell_h = cell_h.max(*h);
+        }
+
+        if cell_w == 0 || cell_h == 0 {
+            return sizes
+                .iter()
+                .map(|(w, h)| Some(Rect::new(0, 0, *w, *h)))
+                .collect();
+        }
+
+        let columns = (bin_w / cell_w) as usize;
+        let rows = (bin_h / cell_h) as usize;
+
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(idx, (w, h))| {
+                if columns == 0 || idx / columns >= rows {
+                    return None;
+                }
+
+                let column = (idx % columns) as u32;
+                let row = (idx / columns) as u32;
+
+                Some(Rect::new(column * cell_w, row * cell_h, *w, *h))
+            })
+            .collect()
+    }
+}
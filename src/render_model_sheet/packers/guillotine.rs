@@ -0,0 +1,69 @@
+//! Guillotine packing; each placement cuts its free rect in two.
+use super::{orientations, sorted_desc, Packer, Rect};
+
+/// Places frames in the free rect they fit best, splitting along the shorter leftover axis.
+pub struct Guillotine {
+    pub allow_rotation: bool,
+}
+
+impl Packer for Guillotine {
+    fn pack(&self, sizes: &[(u32, u32)], bin_w: u32, bin_h: u32) -> Vec<Option<Rect>> {
+        let order = sorted_desc(sizes, |w, h| (w * h, w.max(h)));
+
+        let mut free_rects = vec![Rect::new(0, 0, bin_w, bin_h)];
+        let mut placements = vec![None; sizes.len()];
+
+        for idx in order {
+            let (w, h) = sizes[idx];
+
+            // Best area fit, then the orientation that leaves the least on the short side
+            let best = free_rects
+                .iter()
+                .enumerate()
+                .flat_map(|(free_idx, f)| {
+                    orientations(w, h, self.allow_rotation)
+                        .into_iter()
+                        .map(move |o| (free_idx, f, o))
+                })
+                .filter(|(_, f, (w, h, _))| f.w >= *w && f.h >= *h)
+                .min_by_key(|(_, f, (w, h, _))| {
+                    let short_side = (f.w - w).min(f.h - h);
+                    (f.w as u64 * f.h as u64, short_side, f.y, f.x)
+                })
+                .map(|(free_idx, _, orientation)| (free_idx, orientation));
+
+            let (free_rect, orientation) = match best {
+                Some((free_idx, orientation)) => (free_rects.swap_remove(free_idx), orientation),
+                None => continue,
+            };
+            let (w, h, _) = orientation;
+
+            let leftover_w = free_rect.w - w;
+            let leftover_h = free_rect.h - h;
+
+            let (right, bottom) = if leftover_w <= leftover_h {
+                // Split horizontally; the bottom gets the full width
+                (
+                    Rect::new(free_rect.x + w, free_rect.y, leftover_w, h),
+                    Rect::new(free_rect.x, free_rect.y + h, free_rect.w, leftover_h),
+                )
+            } else {
+                // Split vertically; the right gets the full height
+                (
+                    Rect::new(free_rect.x + w, free_rect.y, leftover_w, free_rect.h),
+                    Rect::new(free_rect.x, free_rect.y + h, w, leftover_h),
+                )
+            };
+
+            for split in [right, bottom].iter() {
+                if split.w > 0 && split.h > 0 {
+                    free_rects.push(*split);
+                }
+            }
+
+            placements[idx] = Some(Rect::placed(free_rect.x, free_rect.y, orientation));
+        }
+
+        placements
+    }
+}
@@ -0,0 +1,253 @@
+//! MaxRects bin packing.
+//! Based on 'A Thousand Ways to Pack the Bin' by Jukka Jylänki.
+use super::{best_placements, orientations, sorted_desc, Packer, Rect};
+
+/// Contact point scoring compares against every used rect, so it's only tried with up to this many rects.
+const CONTACT_POINT_MAX_RECTS: usize = 250;
+
+/// Packs with MaxRects, keeping the best result of the heuristics.
+pub struct MaxRectsPacker {
+    pub allow_rotation: bool,
+}
+
+impl Packer for MaxRectsPacker {
+    fn pack(&self, sizes: &[(u32, u32)], bin_w: u32, bin_h: u32) -> Vec<Option<Rect>> {
+        let order = sorted_desc(sizes, |w, h| (w.max(h), w * h));
+
+        let heuristics = FreeRectChoiceHeuristic::all();
+        let heuristics = heuristics.iter().filter(|heuristic| {
+            **heuristic != FreeRectChoiceHeuristic::ContactPointRule
+                || sizes.len() <= CONTACT_POINT_MAX_RECTS
+        });
+
+        best_placements(heuristics.map(|heuristic| {
+            let mut bin = MaxRects::new(bin_w, bin_h, self.allow_rotation);
+            let mut placements = vec![None; sizes.len()];
+
+            for idx in order.iter() {
+                let (w, h) = sizes[*idx];
+                placements[*idx] = bin.insert(w, h, *heuristic);
+            }
+
+            placements
+        }))
+    }
+}
+
+/// The heuristic used to pick which free rect a new rect gets placed in.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FreeRectChoiceHeuristic {
+    /// Positions the rect against the short side of a free rect it fits best in.
+    BestShortSideFit,
+    /// Positions the rect against the long side of a free rect it fits best in.
+    BestLongSideFit,
+    /// Positions the rect in the smallest free rect it fits in.
+    BestAreaFit,
+    /// Tetris style placement; as low as possible, then as far left as possible.
+    BottomLeftRule,
+    /// Places the rect where it touches other rects or the bin edges the most.
+    ContactPointRule,
+}
+
+impl FreeRectChoiceHeuristic {
+    pub fn all() -> [Self; 5] {
+        [
+            Self::BestShortSideFit,
+            Self::BestLongSideFit,
+            Self::BestAreaFit,
+            Self::BottomLeftRule,
+            Self::ContactPointRule,
+        ]
+    }
+}
+
+/// A single MaxRects bin.
+pub struct MaxRects {
+    allow_rotation: bool,
+    bin_w: u32,
+    bin_h: u32,
+    free_rects: Vec<Rect>,
+    used_rects: Vec<Rect>,
+}
+
+impl MaxRects {
+    pub fn new(bin_w: u32, bin_h: u32, allow_rotation: bool) -> Self {
+        Self {
+            allow_rotation,
+            bin_w,
+            bin_h,
+            free_rects: vec![Rect::new(0, 0, bin_w, bin_h)],
+            used_rects: vec![],
+        }
+    }
+
+    /// Attempts to insert a rect of the given size. Returns the placement if it fit.
+    /// If rotation is allowed, the rect may be placed rotated.
+    pub fn insert(&mut self, w: u32, h: u32, heuristic: FreeRectChoiceHeuristic) -> Option<Rect> {
+        let placed = self.find_position(w, h, heuristic)?;
+
+        // Split every free rect the new rect overlaps
+        let mut new_free_rects = vec![];
+        let mut idx = 0;
+        while idx < self.free_rects.len() {
+            let free_rect = self.free_rects[idx];
+            if free_rect.intersects(&placed) {
+                self.free_rects.swap_remove(idx);
+                split_free_rect(free_rect, placed, &mut new_free_rects);
+            } else {
+                idx += 1;
+            }
+        }
+
+        self.prune_free_rects(new_free_rects);
+        self.used_rects.push(placed);
+
+        Some(placed)
+    }
+
+    /// Finds the best free position, scoring where lower is better.
+    fn find_position(&self, w: u32, h: u32, heuristic: FreeRectChoiceHeuristic) -> Option<Rect> {
+        let mut best: Option<(Rect, (i64, i64))> = None;
+
+        for free_rect in self.free_rects.iter() {
+            for orientation in orientations(w, h, self.allow_rotation) {
+                let (w, h, _) = orientation;
+                if free_rect.w < w || free_rect.h < h {
+                    continue;
+                }
+
+                let leftover_w = (free_rect.w - w) as i64;
+                let leftover_h = (free_rect.h - h) as i64;
+                let short_side = leftover_w.min(leftover_h);
+                let long_side = leftover_w.max(leftover_h);
+
+                let candidate = Rect::placed(free_rect.x, free_rect.y, orientation);
+                let score = match heuristic {
+                    FreeRectChoiceHeuristic::BestShortSideFit => (short_side, long_side),
+                    FreeRectChoiceHeuristic::BestLongSideFit => (long_side, short_side),
+                    FreeRectChoiceHeuristic::BestAreaFit => {
+                        let area_fit =
+                            free_rect.w as i64 * free_rect.h as i64 - w as i64 * h as i64;
+                        (area_fit, short_side)
+                    }
+                    FreeRectChoiceHeuristic::BottomLeftRule => {
+                        (candidate.bottom() as i64, candidate.x as i64)
+                    }
+                    FreeRectChoiceHeuristic::ContactPointRule => {
+                        // Maximize contact, so negate it
+                        (-(self.contact_point_score(&candidate) as i64), 0)
+                    }
+                };
+
+                let is_better = match &best {
+                    Some((_, best_score)) => score < *best_score,
+                    None => true,
+                };
+
+                if is_better {
+                    best = Some((candidate, score));
+                }
+            }
+        }
+
+        best.map(|(rect, _)| rect)
+    }
+
+    /// Returns the length of the edges shared between the rect and the bin or other used rects.
+    fn contact_point_score(&self, rect: &Rect) -> u32 {
+        let mut score = 0;
+
+        if rect.x == 0 || rect.right() == self.bin_w {
+            score += rect.h;
+        }
+        if rect.y == 0 || rect.bottom() == self.bin_h {
+            score += rect.w;
+        }
+
+        for used in self.used_rects.iter() {
+            if used.x == rect.right() || used.right() == rect.x {
+                score += common_interval(used.y, used.bottom(), rect.y, rect.bottom());
+            }
+            if used.y == rect.bottom() || used.bottom() == rect.y {
+                score += common_interval(used.x, used.right(), rect.x, rect.right());
+            }
+        }
+
+        score
+    }
+
+    /// Adds the free rects split off by the last insert, dropping any fully contained by another free rect.
+    /// The existing free rects were already pruned against each other, and none of them can be inside a new one as
+    /// the new ones are pieces of the rects that were split. So only the new rects need checking.
+    fn prune_free_rects(&mut self, mut new_free_rects: Vec<Rect>) {
+        let mut i = 0;
+        while i < new_free_rects.len() {
+            let rect = new_free_rects[i];
+            let is_contained = self.free_rects.iter().any(|f| rect.is_contained_in(f))
+                || new_free_rects
+                    .iter()
+                    .enumerate()
+                    .any(|(j, f)| j != i && rect.is_contained_in(f) && (rect != *f || j < i));
+
+            if is_contained {
+                new_free_rects.swap_remove(i);
+            } else {
+                i += 1;
+            }
+        }
+
+        self.free_rects.append(&mut new_free_rects);
+    }
+}
+
+/// Splits the free rect into the (up to four) maximal rects left around the used rect.
+fn split_free_rect(free_rect: Rect, used: Rect, free_rects: &mut Vec<Rect>) {
+    // Left
+    if used.x > free_rect.x {
+        free_rects.push(Rect::new(
+            free_rect.x,
+            free_rect.y,
+            used.x - free_rect.x,
+            free_rect.h,
+        ));
+    }
+
+    // Right
+    if used.right() < free_rect.right() {
+        free_rects.push(Rect::new(
+            used.right(),
+            free_rect.y,
+            free_rect.right() - used.right(),
+            free_rect.h,
+        ));
+    }
+
+    // Top
+    if used.y > free_rect.y {
+        free_rects.push(Rect::new(
+            free_rect.x,
+            free_rect.y,
+            free_rect.w,
+            used.y - free_rect.y,
+        ));
+    }
+
+    // Bottom
+    if used.bottom() < free_rect.bottom() {
+        free_rects.push(Rect::new(
+            free_rect.x,
+            used.bottom(),
+            free_rect.w,
+            free_rect.bottom() - used.bottom(),
+        ));
+    }
+}
+
+/// Returns the length of the overlap between the two intervals.
+fn common_interval(a_start: u32, a_end: u32, b_start: u32, b_end: u32) -> u32 {
+    if a_end < b_start || b_end < a_start {
+        return 0;
+    }
+
+    a_end.min(b_end) - a_start.max(b_start)
+}
@@ -0,0 +1,486 @@
+mod grid;
+mod guillotine;
+mod max_rects;
+mod shelf;
+mod skyline;
+
+use crate::cfg::PackerKind;
+
+/// How much of the used area needs to be covered by rects before searching for better placements stops.
+const GOOD_OCCUPANCY: f64 = 0.95;
+
+/// A rectangle in the sprite sheet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub w: u32,
+    pub h: u32,
+    /// Whether the frame was rotated 90 degrees clockwise to fit; the width and height are as placed.
+    pub rotated: bool,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, w: u32, h: u32) -> Self {
+        Self {
+            x,
+            y,
+            w,
+            h,
+            rotated: false,
+        }
+    }
+
+    /// Returns the rect with the given position, rotating the size if needed.
+    fn placed(x: u32, y: u32, orientation: Orientation) -> Self {
+        let (w, h, rotated) = orientation;
+
+        Self {
+            x,
+            y,
+            w,
+            h,
+            rotated,
+        }
+    }
+
+    pub fn right(&self) -> u32 {
+        self.x + self.w
+    }
+
+    pub fn bottom(&self) -> u32 {
+        self.y + self.h
+    }
+
+    /// Returns whether this rect is entirely inside the other.
+    pub fn is_contained_in(&self, other: &Rect) -> bool {
+        self.x >= other.x
+            && self.y >= other.y
+            && self.right() <= other.right()
+            && self.bottom() <= other.bottom()
+    }
+
+    /// Returns whether this rect overlaps the other.
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+}
+
+/// A strategy for laying out frames in a bin.
+pub trait Packer {
+    /// Places the given (width, height) sizes in a bin of the given dimensions.
+    /// Returns a placement for each size, in the same order as the input, or `None` if it didn't fit.
+    fn pack(&self, sizes: &[(u32, u32)], bin_w: u32, bin_h: u32) -> Vec<Option<Rect>>;
+}
+
+/// Returns the packer for the given kind.
+/// Grid and shelf packing never rotate frames, since their rows are already fit to the frames.
+pub fn packer(kind: PackerKind, allow_rotation: bool) -> Box<dyn Packer> {
+    let make = |allow_rotation| -> Box<dyn Packer> {
+        match kind {
+            PackerKind::Grid => Box::new(grid::Grid),
+            PackerKind::Guillotine => Box::new(guillotine::Guillotine { allow_rotation }),
+            PackerKind::MaxRects => Box::new(max_rects::MaxRectsPacker { allow_rotation }),
+            PackerKind::Shelf => Box::new(shelf::Shelf),
+            PackerKind::Skyline => Box::new(skyline::Skyline { allow_rotation }),
+        }
+    };
+
+    // Rotating greedily can do worse than not rotating at all, so try both
+    if allow_rotation {
+        Box::new(BestOf(vec![make(false), make(true)]))
+    } else {
+        make(false)
+    }
+}
+
+/// Runs each packer, keeping the best placements.
+struct BestOf(Vec<Box<dyn Packer>>);
+
+impl Packer for BestOf {
+    fn pack(&self, sizes: &[(u32, u32)], bin_w: u32, bin_h: u32) -> Vec<Option<Rect>> {
+        best_placements(self.0.iter().map(|p| p.pack(sizes, bin_w, bin_h)))
+    }
+}
+
+/// Returns the placements that fit the most rects, then cover the smallest area.
+/// Stops early once every rect is placed with a good occupancy.
+fn best_placements<I>(candidates: I) -> Vec<Option<Rect>>
+where
+    I: Iterator<Item = Vec<Option<Rect>>>,
+{
+    let mut best: Option<(usize, u64, Vec<Option<Rect>>)> = None;
+    for placements in candidates {
+        let placed: Vec<Rect> = placements.iter().filter_map(|p| *p).collect();
+        let (used_w, used_h) = used_size(&placed);
+        let used_area = used_w as u64 * used_h as u64;
+
+        let is_better = match &best {
+            Some((best_placed, best_area, _)) => {
+                placed.len() > *best_placed
+                    || (placed.len() == *best_placed && used_area < *best_area)
+            }
+            None => true,
+        };
+
+        if is_better {
+            let is_done =
+                placed.len() == placements.len() && occupancy(&placed, used_area) >= GOOD_OCCUPANCY;
+
+            best = Some((placed.len(), used_area, placements));
+
+            if is_done {
+                break;
+            }
+        }
+    }
+
+    best.map(|(_, _, p)| p).unwrap_or_default()
+}
+
+/// A (width, height, rotated) size to try placing.
+type Orientation = (u32, u32, bool);
+
+/// Returns the orientations a frame may be placed in.
+fn orientations(w: u32, h: u32, allow_rotation: bool) -> Vec<Orientation> {
+    if allow_rotation && w != h {
+        vec![(w, h, false), (h, w, true)]
+    } else {
+        vec![(w, h, false)]
+    }
+}
+
+/// Packs the given (width, height) sizes into the smallest sheet found.
+/// Returns the sheet dimensions along with a placement for each size, in the same order as the input.
+pub fn pack(packer: &dyn Packer, sizes: &[(u32, u32)]) -> Option<(u32, u32, Vec<Rect>)> {
+    if sizes.is_empty() {
+        return None;
+    }
+
+    let mut area = 0;
+    let mut max_w = 0;
+    let mut max_h = 0;
+    for (w, h) in sizes.iter() {
+        area += (*w as u64) * (*h as u64);
+        max_w = max_w.max(*w);
+        max_h = max_h.max(*h);
+    }
+
+    // Try a handful of sheet widths around a square layout, until one is packed well enough. The height is large
+    // enough to hold every frame on its own row, so the sheet gets trimmed to what actually got used.
+    let bin_h = max_h * sizes.len() as u32;
+    let square_w = (area as f64).sqrt();
+    let mut best: Option<(u32, u32, Vec<Rect>)> = None;
+
+    for scale in [1.0, 1.125, 1.25, 1.5, 2.0].iter() {
+        let bin_w = ((square_w * scale).ceil() as u32).max(max_w);
+
+        let placements: Option<Vec<Rect>> = packer.pack(sizes, bin_w, bin_h).into_iter().collect();
+
+        let placements = match placements {
+            Some(p) => p,
+            None => continue,
+        };
+
+        let (sheet_w, sheet_h) = used_size(&placements);
+        if is_smaller(sheet_w, sheet_h, &best) {
+            let used_area = sheet_w as u64 * sheet_h as u64;
+            let is_done = occupancy(&placements, used_area) >= GOOD_OCCUPANCY;
+
+            best = Some((sheet_w, sheet_h, placements));
+
+            if is_done {
+                break;
+            }
+        }
+    }
+
+    best
+}
+
+/// Frames packed across one or more pages.
+pub struct PackedPages {
+    /// The (width, height) of each page.
+    pub page_sizes: Vec<(u32, u32)>,
+    /// The page index and placement for each size, in the same order as the input.
+    pub placements: Vec<(usize, Rect)>,
+}
+
+/// Packs the given (width, height) sizes into as many pages as needed, none larger than the max size.
+pub fn pack_pages(
+    packer: &dyn Packer,
+    sizes: &[(u32, u32)],
+    max_size: Option<u32>,
+) -> Result<PackedPages, String> {
+    let fits = |w: u32, h: u32| match max_size {
+        Some(max_size) => w <= max_size && h <= max_size,
+        None => true,
+    };
+
+    if let Some((w, h)) = sizes.iter().find(|(w, h)| !fits(*w, *h)) {
+        return Err(format!(
+            "A {}x{} px frame is larger than the max texture size.",
+            w, h
+        ));
+    }
+
+    let mut pages = vec![];
+    let mut placements = vec![None; sizes.len()];
+    let mut remaining: Vec<usize> = (0..sizes.len()).collect();
+
+    while !remaining.is_empty() {
+        let page = pages.len();
+        let remaining_sizes: Vec<(u32, u32)> = remaining.iter().map(|idx| sizes[*idx]).collect();
+
+        // Everything left may fit on a single tightly packed page
+        if let Some((w, h, rects)) = pack(packer, &remaining_sizes) {
+            if fits(w, h) {
+                for (idx, rect) in remaining.iter().zip(rects) {
+                    placements[*idx] = Some((page, rect));
+                }
+
+                pages.push((w, h));
+                break;
+            }
+        }
+
+        // Otherwise fill up a max sized page and carry the rest over
+        let max_size = match max_size {
+            Some(max_size) => max_size,
+            None => return Err("Unable to pack frames on a single page.".to_string()),
+        };
+
+        let mut placed = vec![];
+        let mut left_over = vec![];
+        for (idx, rect) in remaining
+            .iter()
+            .zip(packer.pack(&remaining_sizes, max_size, max_size))
+        {
+            match rect {
+                Some(rect) => {
+                    placements[*idx] = Some((page, rect));
+                    placed.push(rect);
+                }
+                None => left_over.push(*idx),
+            }
+        }
+
+        if placed.is_empty() {
+            return Err(format!("Unable to place any frames on page {}.", page));
+        }
+
+        pages.push(used_size(&placed));
+        remaining = left_over;
+    }
+
+    Ok(PackedPages {
+        page_sizes: pages,
+        placements: placements.into_iter().map(|p| p.unwrap()).collect(),
+    })
+}
+
+/// Returns the size of the area covered by the placements.
+fn used_size(placements: &[Rect]) -> (u32, u32) {
+    let mut w = 0;
+    let mut h = 0;
+    for p in placements.iter() {
+        w = w.max(p.right());
+        h = h.max(p.bottom());
+    }
+
+    (w, h)
+}
+
+/// Returns how much of the used area the placements cover, from 0 to 1.
+fn occupancy(placements: &[Rect], used_area: u64) -> f64 {
+    let area: u64 = placements.iter().map(|p| p.w as u64 * p.h as u64).sum();
+
+    area as f64 / used_area.max(1) as f64
+}
+
+/// Returns whether the sheet is smaller than the best one so far.
+/// Ties go to the more square sheet.
+fn is_smaller(w: u32, h: u32, best: &Option<(u32, u32, Vec<Rect>)>) -> bool {
+    match best {
+        Some((best_w, best_h, _)) => {
+            let area = w as u64 * h as u64;
+            let best_area = *best_w as u64 * *best_h as u64;
+
+            area < best_area || (area == best_area && w.max(h) < (*best_w).max(*best_h))
+        }
+        None => true,
+    }
+}
+
+/// Returns the indexes of the sizes, sorted by the given key from largest to smallest.
+fn sorted_desc<K, F>(sizes: &[(u32, u32)], key: F) -> Vec<usize>
+where
+    K: Ord,
+    F: Fn(u32, u32) -> K,
+{
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by(|a, b| {
+        let (aw, ah) = sizes[*a];
+        let (bw, bh) = sizes[*b];
+        key(bw, bh).cmp(&key(aw, ah))
+    });
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [PackerKind; 5] = [
+        PackerKind::Grid,
+        PackerKind::Guillotine,
+        PackerKind::MaxRects,
+        PackerKind::Shelf,
+        PackerKind::Skyline,
+    ];
+
+    /// A xorshift generator, so every run packs the same sizes.
+    struct Rng(u64);
+
+    impl Rng {
+        fn range(&mut self, min: u32, max: u32) -> u32 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+
+            min + (self.0 % (max - min + 1) as u64) as u32
+        }
+
+        fn sizes(&mut self, count: usize, max_w: u32, max_h: u32) -> Vec<(u32, u32)> {
+            (0..count)
+                .map(|_| (self.range(1, max_w), self.range(1, max_h)))
+                .collect()
+        }
+    }
+
+    /// Checks each placement is the size it was given, inside the bin, and that none overlap.
+    fn assert_valid(sizes: &[(u32, u32)], placements: &[Option<Rect>], bin_w: u32, bin_h: u32) {
+        assert_eq!(sizes.len(), placements.len());
+
+        let placed: Vec<Rect> = placements.iter().filter_map(|p| *p).collect();
+        for ((w, h), rect) in sizes.iter().zip(placements.iter()) {
+            if let Some(rect) = rect {
+                let expected = if rect.rotated { (*h, *w) } else { (*w, *h) };
+                assert_eq!(expected, (rect.w, rect.h));
+                assert!(
+                    rect.right() <= bin_w && rect.bottom() <= bin_h,
+                    "{:?}",
+                    rect
+                );
+            }
+        }
+
+        for (i, a) in placed.iter().enumerate() {
+            for b in placed.iter().skip(i + 1) {
+                assert!(!a.intersects(b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn packers_place_rects_inside_the_bin_without_overlapping() {
+        let mut rng = Rng(0x2545f491);
+
+        for kind in KINDS.iter() {
+            for allow_rotation in [false, true].iter() {
+                let packer = packer(*kind, *allow_rotation);
+
+                for _ in 0..25 {
+                    let count = rng.range(1, 60) as usize;
+                    let sizes = rng.sizes(count, 80, 110);
+                    let (bin_w, bin_h) = (rng.range(110, 400), rng.range(110, 400));
+
+                    let placements = packer.pack(&sizes, bin_w, bin_h);
+                    assert_valid(&sizes, &placements, bin_w, bin_h);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packers_only_rotate_when_allowed() {
+        let sizes = vec![(10, 90), (90, 10), (30, 60), (60, 30)];
+
+        for kind in KINDS.iter() {
+            let placements = packer(*kind, false).pack(&sizes, 100, 100);
+            assert!(placements.iter().flatten().all(|p| !p.rotated));
+        }
+    }
+
+    #[test]
+    fn pack_places_every_rect_in_the_sheet() {
+        let mut rng = Rng(0x9e3779b9);
+
+        for kind in KINDS.iter() {
+            let sizes = rng.sizes(200, 40, 60);
+            let (w, h, rects) = pack(packer(*kind, true).as_ref(), &sizes).unwrap();
+
+            let placements: Vec<Option<Rect>> = rects.into_iter().map(Some).collect();
+            assert_valid(&sizes, &placements, w, h);
+        }
+    }
+
+    #[test]
+    fn pack_without_sizes_is_none() {
+        assert!(pack(packer(PackerKind::MaxRects, false).as_ref(), &[]).is_none());
+    }
+
+    #[test]
+    fn pack_pages_keeps_pages_within_the_max_size() {
+        let mut rng = Rng(0x85ebca6b);
+
+        for kind in KINDS.iter() {
+            let sizes = rng.sizes(150, 64, 64);
+            let packed = pack_pages(packer(*kind, false).as_ref(), &sizes, Some(128)).unwrap();
+
+            assert!(packed.page_sizes.len() > 1);
+            for (w, h) in packed.page_sizes.iter() {
+                assert!(*w <= 128 && *h <= 128);
+            }
+
+            for (page_idx, (w, h)) in packed.page_sizes.iter().enumerate() {
+                let placements: Vec<Option<Rect>> = packed
+                    .placements
+                    .iter()
+                    .map(|(page, rect)| Some(*rect).filter(|_| *page == page_idx))
+                    .collect();
+
+                assert_valid(&sizes, &placements, *w, *h);
+            }
+        }
+    }
+
+    #[test]
+    fn pack_pages_uses_a_single_page_without_a_max_size() {
+        let sizes = Rng(7).sizes(100, 64, 64);
+        let packed =
+            pack_pages(packer(PackerKind::MaxRects, false).as_ref(), &sizes, None).unwrap();
+
+        assert_eq!(1, packed.page_sizes.len());
+        assert!(packed.placements.iter().all(|(page, _)| *page == 0));
+    }
+
+    #[test]
+    fn pack_pages_rejects_frames_larger_than_the_max_size() {
+        let sizes = vec![(10, 10), (200, 20)];
+        let result = pack_pages(
+            packer(PackerKind::MaxRects, false).as_ref(),
+            &sizes,
+            Some(128),
+        );
+
+        assert_eq!(
+            Some("A 200x20 px frame is larger than the max texture size.".to_string()),
+            result.err()
+        );
+    }
+}
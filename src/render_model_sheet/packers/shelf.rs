@@ -0,0 +1,51 @@
+//! Shelf packing; fast, but leaves gaps above shorter frames.
+use super::{sorted_desc, Packer, Rect};
+
+/// Places frames in rows, putting each in the first shelf it fits on.
+pub struct Shelf;
+
+struct ShelfRow {
+    y: u32,
+    h: u32,
+    used_w: u32,
+}
+
+impl Packer for Shelf {
+    fn pack(&self, sizes: &[(u32, u32)], bin_w: u32, bin_h: u32) -> Vec<Option<Rect>> {
+        let order = sorted_desc(sizes, |w, h| (h, w));
+
+        let mut shelves: Vec<ShelfRow> = vec![];
+        let mut placements = vec![None; sizes.len()];
+
+        for idx in order {
+            let (w, h) = sizes[idx];
+            if w > bin_w {
+                continue;
+            }
+
+            // Try existing shelves first
+            let shelf = shelves
+                .iter_mut()
+                .find(|s| h <= s.h && s.used_w + w <= bin_w);
+
+            let shelf = match shelf {
+                Some(s) => s,
+                None => {
+                    // Open a new shelf underneath the last one
+                    let y = shelves.last().map(|s| s.y + s.h).unwrap_or_default();
+                    if y + h > bin_h {
+                        continue;
+                    }
+
+                    shelves.push(ShelfRow { y, h, used_w: 0 });
+                    shelves.last_mut().unwrap()
+                }
+            };
+
+            placements[idx] = Some(Rect::new(shelf.used_w, shelf.y, w, h));
+            shelf.used_w += w;
+        }
+
+        placements
+    }
+}
@@ -0,0 +1,132 @@
+//! Skyline packing using the bottom left rule.
+use super::{orientations, sorted_desc, Packer, Rect};
+
+/// Tracks the top edge of the packed frames and drops each new frame as low as it can go.
+pub struct Skyline {
+    pub allow_rotation: bool,
+}
+
+/// A horizontal segment of the skyline.
+#[derive(Copy, Clone, Debug)]
+struct Segment {
+    x: u32,
+    y: u32,
+    w: u32,
+}
+
+impl Packer for Skyline {
+    fn pack(&self, sizes: &[(u32, u32)], bin_w: u32, bin_h: u32) -> Vec<Option<Rect>> {
+        let order = sorted_desc(sizes, |w, h| (h, w));
+
+        let mut skyline = vec![Segment {
+            x: 0,
+            y: 0,
+            w: bin_w,
+        }];
+        let mut placements = vec![None; sizes.len()];
+
+        for idx in order {
+            let (w, h) = sizes[idx];
+
+            // Find the lowest spot, breaking ties by going left
+            let mut best: Option<(usize, Rect)> = None;
+            for segment_idx in 0..skyline.len() {
+                for orientation in orientations(w, h, self.allow_rotation) {
+                    let (w, h, _) = orientation;
+                    if let Some(y) = fits(&skyline, segment_idx, w, h, bin_w, bin_h) {
+                        let rect = Rect::placed(skyline[segment_idx].x, y, orientation);
+                        let is_better = match &best {
+                            Some((_, b)) => (rect.bottom(), rect.x) < (b.bottom(), b.x),
+                            None => true,
+                        };
+
+                        if is_better {
+                            best = Some((segment_idx, rect));
+                        }
+                    }
+                }
+            }
+
+            if let Some((segment_idx, rect)) = best {
+                add_segment(&mut skyline, segment_idx, &rect);
+                placements[idx] = Some(rect);
+            }
+        }
+
+        placements
+    }
+}
+
+/// Returns the y position the rect would sit at if placed on the given segment.
+fn fits(
+    skyline: &[Segment],
+    segment_idx: usize,
+    w: u32,
+    h: u32,
+    bin_w: u32,
+    bin_h: u32,
+) -> Option<u32> {
+    let x = skyline[segment_idx].x;
+    if x + w > bin_w {
+        return None;
+    }
+
+    let mut y = 0;
+    let mut width_left = w;
+    let mut idx = segment_idx;
+
+    // Rest on the highest segment the rect spans
+    while width_left > 0 && idx < skyline.len() {
+        y = y.max(skyline[idx].y);
+        if y + h > bin_h {
+            return None;
+        }
+
+        width_left -= width_left.min(skyline[idx].w);
+        idx += 1;
+    }
+
+    Some(y)
+}
+
+/// Raises the skyline to the top of the placed rect.
+fn add_segment(skyline: &mut Vec<Segment>, segment_idx: usize, rect: &Rect) {
+    skyline.insert(
+        segment_idx,
+        Segment {
+            x: rect.x,
+            y: rect.bottom(),
+            w: rect.w,
+        },
+    );
+
+    // Shrink or remove the segments now covered by the new one
+    let idx = segment_idx + 1;
+    while idx < skyline.len() {
+        let covered_to = rect.right();
+        let segment = skyline[idx];
+        if segment.x >= covered_to {
+            break;
+        }
+
+        let segment_right = segment.x + segment.w;
+        if segment_right <= covered_to {
+            skyline.remove(idx);
+        } else {
+            skyline[idx].x = covered_to;
+            skyline[idx].w = segment_right - covered_to;
+            break;
+        }
+    }
+
+    // Merge neighbors at the same height
+    let mut idx = 0;
+    while idx + 1 < skyline.len() {
+        if skyline[idx].y == skyline[idx + 1].y {
+            skyline[idx].w += skyline[idx + 1].w;
+            skyline.remove(idx + 1);
+        } else {
+            idx += 1;
+        }
+    }
+}
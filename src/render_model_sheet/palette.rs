@@ -0,0 +1,354 @@
+use crate::cfg::MAX_PALETTE_COLORS;
+use benchy::Benchy;
+use image::io::Reader as ImageReader;
+use image::RgbaImage;
+use std::collections::HashMap;
+
+/// Pixels with an alpha at or above this are opaque once quantized, and the rest are transparent.
+pub const OPAQUE_ALPHA: u8 = 128;
+
+/// The colors every sheet in a run is restricted to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    pub colors: Vec<[u8; 3]>,
+}
+
+impl Palette {
+    /// Loads a palette from a GIMP .gpl, a .hex with one color per line, or a .png swatch.
+    pub fn load<'a>(path: &'a str) -> Result<Self, String> {
+        let extension = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+
+        let colors = match extension.as_str() {
+            "gpl" => parse_gpl(&read(path)?)?,
+            "hex" => parse_hex(&read(path)?)?,
+            "png" => {
+                let img = ImageReader::open(path)
+                    .map_err(|e| e.to_string())?
+                    .decode()
+                    .map_err(|e| e.to_string())?;
+
+                // Transparent swatches aren't colors
+                img.into_rgba8()
+                    .pixels()
+                    .filter(|p| p[3] != 0)
+                    .map(|p| [p[0], p[1], p[2]])
+                    .collect()
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown palette format '{}'. Expected one of: gpl, hex, png.",
+                    extension
+                ))
+            }
+        };
+
+        // Keep the first of any repeated colors
+        let mut unique: Vec<[u8; 3]> = vec![];
+        for color in colors {
+            if !unique.contains(&color) {
+                unique.push(color);
+            }
+        }
+
+        if unique.is_empty() {
+            return Err(format!("Palette '{}' doesn't have any colors.", path));
+        }
+
+        if unique.len() > MAX_PALETTE_COLORS as usize {
+            return Err(format!(
+                "Palette '{}' has {} colors, but at most {} are supported.",
+                path,
+                unique.len(),
+                MAX_PALETTE_COLORS
+            ));
+        }
+
+        Ok(Self { colors: unique })
+    }
+
+    /// Generates a palette of up to the given number of colors that best covers the images, by median cut.
+    pub fn generate(imgs: &[&RgbaImage], num_colors: u32) -> Self {
+        Benchy::time("generate_palette");
+
+        // Weight each color by how often it's used
+        let mut counts: HashMap<[u8; 3], u32> = HashMap::new();
+        for img in imgs.iter() {
+            for p in img.pixels().filter(|p| p[3] >= OPAQUE_ALPHA) {
+                *counts.entry([p[0], p[1], p[2]]).or_default() += 1;
+            }
+        }
+
+        let mut colors: Vec<([u8; 3], u32)> = counts.into_iter().collect();
+        colors.sort();
+
+        // Split the box with the widest channel until there are enough
+        let mut boxes = vec![colors];
+        while boxes.len() < num_colors as usize {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, b)| b.len() > 1)
+                .map(|(idx, b)| {
+                    let (channel, range) = widest_channel(b);
+                    (range, idx, channel)
+                })
+                .max();
+
+            let (idx, channel) = match widest {
+                Some((_, idx, channel)) => (idx, channel),
+                None => break,
+            };
+
+            let mut b = boxes.swap_remove(idx);
+            b.sort_by_key(|(color, _)| color[channel]);
+
+            // Split at the weighted median, leaving at least one color on each side
+            let total: u32 = b.iter().map(|(_, count)| count).sum();
+            let mut below = 0;
+            let mut split = 1;
+            for (i, (_, count)) in b.iter().enumerate() {
+                below += count;
+                if below * 2 >= total {
+                    split = (i + 1).min(b.len() - 1);
+                    break;
+                }
+            }
+
+            let upper = b.split_off(split);
+            boxes.push(b);
+            boxes.push(upper);
+        }
+
+        // Each box becomes its weighted average color
+        let colors = boxes
+            .iter()
+            .filter(|b| !b.is_empty())
+            .map(|b| {
+                let mut sum = [0u64; 3];
+                let mut total = 0u64;
+                for (color, count) in b.iter() {
+                    for c in 0..3 {
+                        sum[c] += color[c] as u64 * *count as u64;
+                    }
+                    total += *count as u64;
+                }
+
+                [
+                    (sum[0] / total) as u8,
+                    (sum[1] / total) as u8,
+                    (sum[2] / total) as u8,
+                ]
+            })
+            .collect();
+
+        Self { colors }
+    }
+
+    /// Returns the index of the closest color.
+    pub fn nearest(&self, color: [f32; 3]) -> usize {
+        let mut nearest = 0;
+        let mut nearest_distance = f32::MAX;
+
+        for (idx, c) in self.colors.iter().enumerate() {
+            let dr = color[0] - c[0] as f32;
+            let dg = color[1] - c[1] as f32;
+            let db = color[2] - c[2] as f32;
+            let distance = dr * dr + dg * dg + db * db;
+
+            if distance < nearest_distance {
+                nearest = idx;
+                nearest_distance = distance;
+            }
+        }
+
+        nearest
+    }
+}
+
+fn read<'a>(path: &'a str) -> Result<String, String> {
+    std::fs::read_to_string(path).map_err(|e| format!("Unable to read '{}': {}", path, e))
+}
+
+/// Parses a GIMP palette, where each color is a line of 'r g b name'.
+fn parse_gpl<'a>(contents: &'a str) -> Result<Vec<[u8; 3]>, String> {
+    let mut colors = vec![];
+
+    for line in contents.lines() {
+        let line = line.trim();
+
+        // Skip the header and comments
+        let is_header = line.starts_with("GIMP Palette")
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:");
+        if line.is_empty() || line.starts_with('#') || is_header {
+            continue;
+        }
+
+        let channels: Vec<Option<u8>> = line
+            .split_whitespace()
+            .take(3)
+            .map(|c| c.parse::<u8>().ok())
+            .collect();
+
+        match channels.as_slice() {
+            [Some(r), Some(g), Some(b)] => colors.push([*r, *g, *b]),
+            _ => return Err(format!("Invalid palette color '{}'.", line)),
+        }
+    }
+
+    Ok(colors)
+}
+
+/// Parses a palette with one 'rrggbb' color per line, optionally starting with '#'.
+fn parse_hex<'a>(contents: &'a str) -> Result<Vec<[u8; 3]>, String> {
+    let mut colors = vec![];
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let hex = line.trim_start_matches('#');
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+        };
+
+        match (hex.len(), channel(0), channel(2), channel(4)) {
+            (6, Some(r), Some(g), Some(b)) => colors.push([r, g, b]),
+            _ => return Err(format!("Invalid palette color '{}'.", line)),
+        }
+    }
+
+    Ok(colors)
+}
+
+/// Returns the channel with the largest range of values, along with that range.
+fn widest_channel(colors: &[([u8; 3], u32)]) -> (usize, u8) {
+    let mut widest = (0, 0);
+
+    for channel in 0..3 {
+        let min = colors
+            .iter()
+            .map(|(c, _)| c[channel])
+            .min()
+            .unwrap_or_default();
+        let max = colors
+            .iter()
+            .map(|(c, _)| c[channel])
+            .max()
+            .unwrap_or_default();
+
+        if max - min > widest.1 {
+            widest = (channel, max - min);
+        }
+    }
+
+    widest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn parse_gpl_skips_the_header_and_comments() {
+        let gpl = "GIMP Palette\nName: Test\nColumns: 2\n# A comment\n\n255   0   0\tRed\n  0 128 255 Sky blue\n";
+
+        assert_eq!(Ok(vec![[255, 0, 0], [0, 128, 255]]), parse_gpl(gpl));
+        assert_eq!(
+            Err("Invalid palette color '255 0'.".to_string()),
+            parse_gpl("255 0\n")
+        );
+        assert_eq!(
+            Err("Invalid palette color '256 0 0'.".to_string()),
+            parse_gpl("256 0 0\n")
+        );
+    }
+
+    #[test]
+    fn parse_hex_reads_a_color_per_line() {
+        assert_eq!(
+            Ok(vec![[255, 0, 128], [0, 16, 32]]),
+            parse_hex("ff0080\n\n#001020\n")
+        );
+        assert_eq!(
+            Err("Invalid palette color 'ff00'.".to_string()),
+            parse_hex("ff00\n")
+        );
+        assert_eq!(
+            Err("Invalid palette color 'ff008080'.".to_string()),
+            parse_hex("ff008080\n")
+        );
+    }
+
+    #[test]
+    fn load_dedupes_colors_and_checks_the_format() {
+        let path = std::env::temp_dir().join(format!("palette_test_{}.hex", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        std::fs::write(path, "ff0000\n00ff00\nff0000\n").unwrap();
+        assert_eq!(
+            Ok(Palette {
+                colors: vec![[255, 0, 0], [0, 255, 0]]
+            }),
+            Palette::load(path)
+        );
+
+        std::fs::write(path, "\n").unwrap();
+        assert_eq!(
+            Err(format!("Palette '{}' doesn't have any colors.", path)),
+            Palette::load(path)
+        );
+
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            Err("Unknown palette format 'act'. Expected one of: gpl, hex, png.".to_string()),
+            Palette::load("colors.act")
+        );
+    }
+
+    #[test]
+    fn generate_keeps_every_color_when_there_are_few_enough() {
+        let colors = [[255, 0, 0], [0, 255, 0], [0, 0, 255]];
+        let img = RgbaImage::from_fn(3, 2, |x, y| {
+            let [r, g, b] = colors[x as usize];
+            // The bottom row is transparent, so its colors don't count
+            Rgba([r, g, b, if y == 0 { 255 } else { 0 }])
+        });
+
+        let mut palette = Palette::generate(&[&img], 8).colors;
+        palette.sort();
+
+        assert_eq!(vec![[0, 0, 255], [0, 255, 0], [255, 0, 0]], palette);
+    }
+
+    #[test]
+    fn generate_averages_clusters_of_colors() {
+        // As many dark red pixels as light blue ones
+        let img = RgbaImage::from_fn(4, 4, |x, _| match x {
+            0 => Rgba([100, 0, 0, 255]),
+            1 => Rgba([120, 0, 0, 255]),
+            2 => Rgba([0, 200, 240, 255]),
+            _ => Rgba([0, 220, 250, 255]),
+        });
+
+        let mut palette = Palette::generate(&[&img], 2).colors;
+        palette.sort();
+
+        assert_eq!(vec![[0, 210, 245], [110, 0, 0]], palette);
+    }
+
+    #[test]
+    fn nearest_finds_the_closest_color() {
+        let palette = Palette {
+            colors: vec![[0, 0, 0], [255, 255, 255], [255, 0, 0]],
+        };
+
+        assert_eq!(0, palette.nearest([20.0, 30.0, 10.0]));
+        assert_eq!(1, palette.nearest([200.0, 220.0, 210.0]));
+        assert_eq!(2, palette.nearest([200.0, 40.0, 30.0]));
+    }
+}
@@ -0,0 +1,196 @@
+use super::palette::{Palette, OPAQUE_ALPHA};
+use crate::cfg::DitherKind;
+use benchy::Benchy;
+use image::{Rgba, RgbaImage};
+use std::collections::HashMap;
+
+/// The index of the transparent color in a quantized image. Palette colors follow it.
+pub const TRANSPARENT_INDEX: u8 = 0;
+
+/// How far the ordered dither pattern may push a channel, either way.
+const ORDERED_SPREAD: f32 = 32.0;
+
+/// A 4x4 Bayer matrix.
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Maps each pixel of the image to an index of the palette, in row order.
+pub fn execute(img: &RgbaImage, palette: &Palette, dither: DitherKind) -> Vec<u8> {
+    Benchy::time("quantize");
+
+    let (w, h) = img.dimensions();
+    let mut indexes = vec![TRANSPARENT_INDEX; (w * h) as usize];
+
+    let is_opaque = |x: u32, y: u32| img.get_pixel(x, y)[3] >= OPAQUE_ALPHA;
+    let color = |x: u32, y: u32| {
+        let p = img.get_pixel(x, y);
+        [p[0] as f32, p[1] as f32, p[2] as f32]
+    };
+    let index = |nearest: usize| nearest as u8 + 1;
+
+    match dither {
+        DitherKind::None => {
+            // Pixel art repeats a lot of colors
+            let mut cache: HashMap<[u8; 3], u8> = HashMap::new();
+
+            for (x, y, p) in img.enumerate_pixels() {
+                if is_opaque(x, y) {
+                    indexes[(y * w + x) as usize] = *cache
+                        .entry([p[0], p[1], p[2]])
+                        .or_insert_with(|| index(palette.nearest(color(x, y))));
+                }
+            }
+        }
+        DitherKind::Ordered => {
+            for (x, y, _) in img.enumerate_pixels() {
+                if is_opaque(x, y) {
+                    let threshold = BAYER[(y % 4) as usize][(x % 4) as usize] as f32 / 16.0 - 0.5;
+                    let offset = threshold * 2.0 * ORDERED_SPREAD;
+                    let [r, g, b] = color(x, y);
+
+                    indexes[(y * w + x) as usize] =
+                        index(palette.nearest([r + offset, g + offset, b + offset]));
+                }
+            }
+        }
+        DitherKind::Diffusion => {
+            // Floyd-Steinberg, only spreading the error to other opaque pixels
+            let mut errors = vec![[0.0f32; 3]; (w * h) as usize];
+
+            for y in 0..h {
+                for x in 0..w {
+                    if !is_opaque(x, y) {
+                        continue;
+                    }
+
+                    let i = (y * w + x) as usize;
+                    let mut wanted = color(x, y);
+                    for c in 0..3 {
+                        wanted[c] = (wanted[c] + errors[i][c]).clamp(0.0, 255.0);
+                    }
+
+                    let nearest = palette.nearest(wanted);
+                    indexes[i] = index(nearest);
+
+                    let got = palette.colors[nearest];
+                    let error = [
+                        wanted[0] - got[0] as f32,
+                        wanted[1] - got[1] as f32,
+                        wanted[2] - got[2] as f32,
+                    ];
+
+                    let neighbours = [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)];
+                    for (dx, dy, weight) in neighbours.iter() {
+                        let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                        if nx < 0 || nx >= w as i64 || ny >= h as i64 {
+                            continue;
+                        }
+
+                        let (nx, ny) = (nx as u32, ny as u32);
+                        if is_opaque(nx, ny) {
+                            let n = (ny * w + nx) as usize;
+                            for c in 0..3 {
+                                errors[n][c] += error[c] * weight / 16.0;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    indexes
+}
+
+/// Converts quantized indexes back into an image.
+pub fn to_rgba(w: u32, h: u32, indexes: &[u8], palette: &Palette) -> RgbaImage {
+    RgbaImage::from_fn(w, h, |x, y| match indexes[(y * w + x) as usize] {
+        TRANSPARENT_INDEX => Rgba([0, 0, 0, 0]),
+        idx => {
+            let [r, g, b] = palette.colors[(idx - 1) as usize];
+            Rgba([r, g, b, 255])
+        }
+    })
+}
+
+/// Encodes quantized indexes as an indexed PNG.
+pub fn indexed_png(w: u32, h: u32, indexes: &[u8], palette: &Palette) -> Vec<u8> {
+    let mut plte = vec![0, 0, 0];
+    for color in palette.colors.iter() {
+        plte.extend_from_slice(color);
+    }
+
+    let mut bytes = vec![];
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, w, h);
+        encoder.set_color(png::ColorType::Indexed);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_palette(plte);
+
+        // Only the first entry is transparent
+        encoder.set_trns(vec![0]);
+
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(indexes).unwrap();
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::io::Reader as ImageReader;
+    use std::io::Cursor;
+
+    fn palette() -> Palette {
+        Palette {
+            colors: vec![[0, 0, 0], [255, 255, 255], [200, 40, 40]],
+        }
+    }
+
+    fn img() -> RgbaImage {
+        RgbaImage::from_fn(4, 2, |x, y| match (x, y) {
+            (0, 0) => Rgba([10, 10, 10, 255]),
+            (1, 0) => Rgba([250, 240, 255, 255]),
+            (2, 0) => Rgba([190, 50, 30, 200]),
+            (3, 0) => Rgba([255, 255, 255, 100]),
+            _ => Rgba([0, 0, 0, 0]),
+        })
+    }
+
+    #[test]
+    fn execute_maps_opaque_pixels_to_the_palette() {
+        let indexes = execute(&img(), &palette(), DitherKind::None);
+
+        assert_eq!(vec![1, 2, 3, 0, 0, 0, 0, 0], indexes);
+    }
+
+    #[test]
+    fn dithering_leaves_transparent_pixels_alone() {
+        for dither in [DitherKind::Ordered, DitherKind::Diffusion].iter() {
+            let indexes = execute(&img(), &palette(), *dither);
+
+            assert!(indexes[0..3].iter().all(|i| *i != TRANSPARENT_INDEX));
+            assert!(indexes[3..].iter().all(|i| *i == TRANSPARENT_INDEX));
+        }
+    }
+
+    #[test]
+    fn indexed_png_decodes_to_the_quantized_image() {
+        let indexes = execute(&img(), &palette(), DitherKind::None);
+        let expected = to_rgba(4, 2, &indexes, &palette());
+
+        assert_eq!(&Rgba([200, 40, 40, 255]), expected.get_pixel(2, 0));
+        assert_eq!(&Rgba([0, 0, 0, 0]), expected.get_pixel(3, 0));
+
+        let bytes = indexed_png(4, 2, &indexes, &palette());
+        let decoded = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()
+            .unwrap()
+            .decode()
+            .unwrap()
+            .into_rgba8();
+
+        assert_eq!(expected, decoded);
+    }
+}
@@ -1,13 +1,33 @@
 use super::{sorted_map::SortedMap, *};
+use crate::cfg::{OutlineKind, PassKind};
 use crate::internal_sheet::*;
-use crate::Render;
+use crate::{Cfg, FrameImgs, Render};
 use benchy::Benchy;
 use image::io::Reader as ImageReader;
-use image::{DynamicImage, GenericImage, GenericImageView};
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use std::path::Path;
 
-pub fn execute(animation: String, mut imgs: Vec<ImgToRender>) -> Render {
+/// Blender's default frame rate, for animations without playback settings.
+const DEFAULT_FPS: f32 = 24.0;
+
+/// Renders each frame of the animation, cropped to its own bounds or to the bounds it shares in the given animation.
+pub fn execute(
+    animation: String,
+    mut imgs: Vec<ImgToRender>,
+    cfg: &Cfg,
+    shared: Option<&Animation>,
+) -> Render {
     Benchy::time("render_animation");
 
+    // Blender writes the playback settings next to the frames
+    let playback = imgs
+        .first()
+        .map(|img| {
+            let path = Path::new(&img.path).with_file_name(format!("{}_ESCAPED.anim", animation));
+            read_playback(path.to_str().unwrap_or_default())
+        })
+        .unwrap_or_default();
+
     // Group directions
     let mut directions: SortedMap<u32, Vec<ImgToRender>> = SortedMap::new();
     while imgs.is_empty() == false {
@@ -21,111 +41,120 @@ pub fn execute(animation: String, mut imgs: Vec<ImgToRender>) -> Render {
     }
 
     // Render each direction
-    let mut directional_renders: Vec<(DirectionalAnimation, DynamicImage)> = vec![];
-    for (direction, imgs) in directions.iter() {
-        let (img, frames) = render_frames(imgs);
-
-        let direction = DirectionalAnimation {
-            start_x_px: 0,
-            start_y_px: 0,
-            direction: *direction,
-            frames,
-        };
-
-        directional_renders.push((direction, img));
-    }
-
-    // Join and return a single animation + render
-    join_directional_renders(animation, directional_renders)
-}
-
-fn join_directional_renders(
-    animation: String,
-    mut directional_renders: Vec<(DirectionalAnimation, DynamicImage)>,
-) -> (Animation, DynamicImage) {
-    let mut final_image = {
-        let mut width = 0;
-        let mut height = 0;
-
-        for (_, render) in directional_renders.iter() {
-            width = width.max(render.width());
-            height += render.height();
-        }
-
-        DynamicImage::new_rgba8(width, height)
-    };
-
     let mut final_animation = Animation {
         start_x_px: 0,
         start_y_px: 0,
         name: animation,
+        fps: playback.fps,
+        frame_step: playback.frame_step,
+        looping: playback.looping,
+        page: 0,
         directional_animations: vec![],
     };
-
-    let offset_x_px = 0;
-    let mut offset_y_px = 0;
-    while directional_renders.is_empty() == false {
-        let (mut dir_animation, img) = directional_renders.remove(0);
-
-        // Update offsets on animation
-        dir_animation.start_x_px = offset_x_px;
-        dir_animation.start_y_px = offset_y_px;
-
-        // Copy image
-        final_image
-            .copy_from(&img, offset_x_px, offset_y_px)
-            .unwrap();
-
-        // Increment offsets
-        offset_y_px += img.height();
-
-        // Add to final animation
-        final_animation.directional_animations.push(dir_animation);
+    let mut final_imgs = vec![];
+
+    for (idx, (direction, imgs)) in directions.iter().iter().enumerate() {
+        let shared = shared.map(|a| &a.directional_animations[idx].frames[..]);
+        let (imgs, frames) = render_frames(imgs, &playback, cfg, shared);
+
+        final_animation
+            .directional_animations
+            .push(DirectionalAnimation {
+                start_x_px: 0,
+                start_y_px: 0,
+                page: 0,
+                direction: *direction,
+                frames,
+            });
+
+        final_imgs.push(imgs);
     }
 
-    (final_animation, final_image)
+    // Positions get filled in when the sheet is packed
+    (final_animation, final_imgs)
 }
 
-fn render_frames(frames_to_render: &Vec<ImgToRender>) -> (DynamicImage, Vec<Frame>) {
+fn render_frames(
+    frames_to_render: &Vec<ImgToRender>,
+    playback: &Playback,
+    cfg: &Cfg,
+    shared: Option<&[Frame]>,
+) -> (Vec<FrameImgs>, Vec<Frame>) {
     let mut frames = vec![];
     let mut frame_renders = vec![];
 
-    let mut height_px = 0;
-    let mut width_px = 0;
-
     // Process each frame
-    for img_to_render in frames_to_render.iter() {
-        let (img, mut frame) = render_frame(img_to_render);
-
-        // Update the start positions for the frame
-        frame.start_x_px = width_px;
-        frame.start_y_px = 0;
+    for (idx, img_to_render) in frames_to_render.iter().enumerate() {
+        let (img, mut frame) = render_frame(img_to_render, cfg, shared.map(|s| &s[idx]));
 
-        // Increase final image height + width
-        height_px = height_px.max(img.height());
-        width_px += img.width();
+        // Each frame lasts until the next one, which is a frame step later unless some are missing
+        let blender_frames = match frames_to_render.get(idx + 1) {
+            Some(next) => next.frame.saturating_sub(img_to_render.frame).max(1),
+            None => playback.frame_step,
+        };
+        frame.duration_ms = (blender_frames as f32 * 1000.0 / playback.fps).round() as u32;
 
-        // Add to collections
         frames.push(frame);
         frame_renders.push(img);
     }
 
-    // Join up images
-    let mut img = DynamicImage::new_rgba8(width_px, height_px);
-    let mut copy_x = 0;
-    let copy_y = 0;
-    for frame_render in frame_renders.iter() {
-        img.copy_from(frame_render, copy_x, copy_y).unwrap();
+    (frame_renders, frames)
+}
 
-        copy_x += frame_render.width();
+fn render_frame(image: &ImgToRender, cfg: &Cfg, shared: Option<&Frame>) -> (FrameImgs, Frame) {
+    let mut img = load(&image.path, cfg);
+
+    // Extra passes go through everything the color pass does
+    let mut passes: Vec<(PassKind, DynamicImage)> = image
+        .pass_paths
+        .iter()
+        .map(|(pass, path)| (*pass, load(path, cfg)))
+        .collect();
+
+    for (pass, pass_img) in passes.iter() {
+        if pass_img.dimensions() != img.dimensions() {
+            panic!(
+                "The {} pass for '{}' is {:?} px, but the color pass is {:?} px.",
+                pass.name(),
+                image.path,
+                pass_img.dimensions(),
+                img.dimensions()
+            );
+        }
     }
 
-    // Return frames + final render
-    (img, frames)
-}
+    // Fall back to keying the team color mask from the color pass
+    let has_team_mask = passes.iter().any(|(p, _)| *p == PassKind::TeamMask);
+    if let (Some(key), false) = (cfg.team_color_key, has_team_mask) {
+        let mask = team_mask::from_color_key(&img, key, cfg.team_color_tolerance);
+        passes.push((PassKind::TeamMask, mask));
+    }
 
-fn render_frame(image: &ImgToRender) -> (DynamicImage, Frame) {
-    let mut img = ImageReader::open(&image.path).unwrap().decode().unwrap();
+    // Outline before cropping, so the crop includes it. Outer outlines may reach past the render, so everything is
+    // padded to make room.
+    let padding = outline_padding(cfg);
+    if padding > 0 {
+        img = pad(img, padding);
+        passes = passes
+            .into_iter()
+            .map(|(pass, pass_img)| (pass, pad(pass_img, padding)))
+            .collect();
+    }
+
+    if cfg.outline != OutlineKind::None {
+        let mut outlined = img.into_rgba8();
+        let outline_pixels = outline::execute(&mut outlined, cfg);
+
+        img = DynamicImage::ImageRgba8(outlined);
+
+        for (pass, pass_img) in passes.iter_mut() {
+            for (x, y) in outline_pixels.iter() {
+                pass_img.put_pixel(*x, *y, outline_fill(*pass));
+            }
+        }
+    }
+
+    let (source_w_px, source_h_px) = img.dimensions();
 
     // Get coordinates to crop
     let mut new_start_x = img.width();
@@ -133,9 +162,13 @@ fn render_frame(image: &ImgToRender) -> (DynamicImage, Frame) {
     let mut new_end_x = 0;
     let mut new_end_y = 0;
 
+    let mut is_empty = true;
+
     for (x, y, rgba) in img.pixels() {
-        // If alpha is not zero, update the coordinates
-        if rgba[3] != 0 {
+        // If alpha is above the threshold, update the coordinates
+        if rgba[3] > cfg.alpha_threshold {
+            is_empty = false;
+
             new_start_x = x.min(new_start_x);
             new_start_y = y.min(new_start_y);
 
@@ -144,6 +177,47 @@ fn render_frame(image: &ImgToRender) -> (DynamicImage, Frame) {
         }
     }
 
+    let pivot_px = image
+        .pivot_path
+        .as_ref()
+        .and_then(|path| read_pivot(path))
+        .map(|(x, y)| {
+            (
+                x / cfg.render_scale as f32 + padding as f32,
+                y / cfg.render_scale as f32 + padding as f32,
+            )
+        });
+
+    // Nothing visible, so keep the frame in the sequence but don't give it any pixels
+    if is_empty {
+        let frame = Frame {
+            duration_ms: 0,
+            empty: true,
+            grid_cell: None,
+            height_px: 0,
+            width_px: 0,
+            offset_x_px: 0,
+            offset_y_px: 0,
+            page: 0,
+            pivot_px,
+            rotated: false,
+            source_w_px,
+            source_h_px,
+            start_x_px: 0,
+            start_y_px: 0,
+        };
+
+        let imgs = FrameImgs {
+            color: DynamicImage::new_rgba8(0, 0),
+            passes: passes
+                .into_iter()
+                .map(|(pass, _)| (pass, DynamicImage::new_rgba8(0, 0)))
+                .collect(),
+        };
+
+        return (imgs, frame);
+    }
+
     // Add a 1px buffer to prevent jitter
     if new_start_x > 0 {
         new_start_x -= 1;
@@ -158,24 +232,150 @@ fn render_frame(image: &ImgToRender) -> (DynamicImage, Frame) {
         new_end_y += 1;
     }
 
-    // Crop img
-    let width_px = new_end_x - new_start_x;
-    let height_px = new_end_y - new_start_y;
-
-    let img = img.crop(new_start_x, new_start_y, width_px, height_px);
+    // Frames sharing bounds with others are cropped to those instead
+    let (offset_x_px, offset_y_px, width_px, height_px) = match shared {
+        Some(shared) => (
+            shared.offset_x_px,
+            shared.offset_y_px,
+            shared.width_px,
+            shared.height_px,
+        ),
+        None => (
+            new_start_x,
+            new_start_y,
+            new_end_x - new_start_x,
+            new_end_y - new_start_y,
+        ),
+    };
 
-    // Keep track of the new offsets
-    let offset_x_px = new_start_x;
-    let offset_y_px = new_start_y;
+    // Crop img, along with the extra passes so they stay in sync
+    let img = img.crop(offset_x_px, offset_y_px, width_px, height_px);
+    let passes = passes
+        .into_iter()
+        .map(|(pass, mut p)| (pass, p.crop(offset_x_px, offset_y_px, width_px, height_px)))
+        .collect();
 
     let frame = Frame {
+        duration_ms: 0,
+        empty: false,
+        grid_cell: None,
         height_px,
         width_px,
         offset_x_px,
         offset_y_px,
+        page: 0,
+        pivot_px,
+        rotated: false,
+        source_w_px,
+        source_h_px,
         start_x_px: 0,
         start_y_px: 0,
     };
 
-    (img, frame)
+    (FrameImgs { color: img, passes }, frame)
+}
+
+/// The value of an outline pixel in an extra pass.
+fn outline_fill(pass: PassKind) -> Rgba<u8> {
+    match pass {
+        // Facing the camera
+        PassKind::Normal => Rgba([128, 128, 255, 255]),
+        // As far away as possible, not glowing, index 0 or not recolored
+        PassKind::Depth
+        | PassKind::Emission
+        | PassKind::ObjectIndex
+        | PassKind::MaterialIndex
+        | PassKind::TeamMask => Rgba([0, 0, 0, 255]),
+    }
+}
+
+/// How far outlines may reach past the edge of the render.
+fn outline_padding(cfg: &Cfg) -> u32 {
+    match cfg.outline {
+        OutlineKind::Outer | OutlineKind::Both => cfg.outline_thickness,
+        OutlineKind::None | OutlineKind::Inner => 0,
+    }
+}
+
+/// Surrounds the image with transparent pixels.
+fn pad(img: DynamicImage, padding: u32) -> DynamicImage {
+    let mut padded = DynamicImage::new_rgba8(img.width() + padding * 2, img.height() + padding * 2);
+    padded.copy_from(&img, padding, padding).unwrap();
+
+    padded
+}
+
+/// Loads a render, shrinking it if it was made at a larger scale.
+fn load<'a>(path: &'a str, cfg: &Cfg) -> DynamicImage {
+    let img = ImageReader::open(path).unwrap().decode().unwrap();
+
+    if cfg.render_scale > 1 {
+        DynamicImage::ImageRgba8(downsample::execute(
+            &img.into_rgba8(),
+            cfg.render_scale,
+            cfg.downsample,
+        ))
+    } else {
+        img
+    }
+}
+
+/// How an animation plays back, as set in Blender.
+struct Playback {
+    fps: f32,
+    frame_step: u32,
+    looping: bool,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            fps: DEFAULT_FPS,
+            frame_step: 1,
+            looping: true,
+        }
+    }
+}
+
+/// Reads the 'name=value' playback settings Blender wrote for an animation.
+/// Anything missing or invalid is left as the default.
+fn read_playback<'a>(path: &'a str) -> Playback {
+    let mut playback = Playback::default();
+    let contents = std::fs::read_to_string(path).unwrap_or_default();
+
+    for line in contents.lines() {
+        let mut split = line.splitn(2, '=').map(|s| s.trim());
+        match (split.next(), split.next()) {
+            (Some("fps"), Some(value)) => {
+                if let Ok(fps) = value.parse::<f32>() {
+                    if fps > 0.0 {
+                        playback.fps = fps;
+                    }
+                }
+            }
+            (Some("frame_step"), Some(value)) => {
+                if let Ok(frame_step) = value.parse::<u32>() {
+                    playback.frame_step = frame_step.max(1);
+                }
+            }
+            (Some("loop"), Some(value)) => playback.looping = value != "false",
+            _ => {}
+        }
+    }
+
+    playback
+}
+
+/// Reads the 'x y' pivot Blender wrote for a frame.
+fn read_pivot<'a>(path: &'a str) -> Option<(f32, f32)> {
+    let contents = std::fs::read_to_string(path).unwrap_or_default();
+    let mut split = contents.split_whitespace().map(|s| s.parse::<f32>().ok());
+
+    match (split.next().flatten(), split.next().flatten()) {
+        (Some(x), Some(y)) => Some((x, y)),
+        _ => {
+            println!("Unable to read pivot from '{}'.", path);
+            None
+        }
+    }
 }
@@ -1,14 +1,34 @@
 use super::{sorted_map::SortedMap, *};
-use crate::Render;
+use crate::cfg::CropPolicy;
+use crate::{Cfg, Render};
 use benchy::Benchy;
 
-pub fn execute(animations: SortedMap<String, Vec<ImgToRender>>) -> Vec<Render> {
+pub fn execute(animations: SortedMap<String, Vec<ImgToRender>>, cfg: &Cfg) -> Vec<Render> {
     Benchy::time("render_animations");
 
+    // Frames that share bounds need every frame's bounds before they can be cropped. Rather than keeping every full
+    // render until then, the bounds are found first and the frames are rendered again once they're shared.
+    let shared = match cfg.crop {
+        CropPolicy::Frame => None,
+        CropPolicy::Direction | CropPolicy::Animation | CropPolicy::Sheet => {
+            let mut shared: Vec<_> = animations
+                .iter()
+                .iter()
+                .map(|(animation, imgs)| {
+                    render_animation::execute(animation.clone(), imgs.clone(), cfg, None).0
+                })
+                .collect();
+
+            crop::execute(&mut shared, cfg.crop);
+            Some(shared)
+        }
+    };
+
     let mut rendered_animations = vec![];
 
-    for (animation, imgs) in animations.iter() {
-        let rendered = render_animation::execute(animation.clone(), imgs.clone());
+    for (idx, (animation, imgs)) in animations.iter().iter().enumerate() {
+        let shared = shared.as_ref().map(|s| &s[idx]);
+        let rendered = render_animation::execute(animation.clone(), imgs.clone(), cfg, shared);
         rendered_animations.push(rendered);
     }
 
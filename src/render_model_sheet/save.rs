@@ -1,14 +1,33 @@
+use crate::cfg::ImageOutput;
 use crate::*;
 use benchy::Benchy;
 use builder_sheet_rust::*;
+use serde_json::{json, Value};
 use std::fs::File;
 use std::io::prelude::*;
 
-pub fn execute<'a>(path: &'a str, file_name: &'a str, sprite_sheet: internal_sheet::SpriteSheet) {
+pub fn execute<'a>(
+    path: &'a str,
+    file_name: &'a str,
+    sprite_sheet: &internal_sheet::SpriteSheet,
+    image_output: ImageOutput,
+) {
     Benchy::time("save");
 
-    let sprite_sheet = map_internal_to_external(sprite_sheet);
-    let json = sprite_sheet.to_json().unwrap();
+    let images = Images {
+        path,
+        file_name,
+        output: image_output,
+    };
+
+    let json = {
+        let external = map_internal_to_external(sprite_sheet);
+        let mut json: Value = serde_json::from_str(&external.to_json().unwrap()).unwrap();
+        extend_json(&mut json, sprite_sheet, &images);
+
+        serde_json::to_string(&json).unwrap()
+    };
+
     let mut file = File::create(format!(
         "{path}{file_name}.json",
         path = path,
@@ -18,33 +37,255 @@ pub fn execute<'a>(path: &'a str, file_name: &'a str, sprite_sheet: internal_she
     file.write_all(json.as_bytes()).unwrap();
 }
 
-fn map_internal_to_external(sprite_sheet: internal_sheet::SpriteSheet) -> SpriteSheet {
-    let image_png_bytes = &sprite_sheet.image_png_bytes;
-    let width_px = sprite_sheet.width_px;
-    let height_px = sprite_sheet.height_px;
-    let animations = map_animations(&sprite_sheet);
+/// Returns the name of a page's image file, without the extension.
+/// Suffixes tell apart the images of layers and the like, and the page index is only added if there's more than one.
+pub fn page_file_name<'a>(
+    file_name: &'a str,
+    suffix: &'a str,
+    page_idx: usize,
+    num_pages: usize,
+) -> String {
+    if num_pages == 1 {
+        format!("{}{}", file_name, suffix)
+    } else {
+        format!("{}{}_{}", file_name, suffix, page_idx)
+    }
+}
+
+/// Where the images of a sheet go.
+struct Images<'a> {
+    path: &'a str,
+    file_name: &'a str,
+    output: ImageOutput,
+}
+
+impl<'a> Images<'a> {
+    /// Adds an image to the JSON object, either embedded as base64 under the embedded key or written next to the
+    /// JSON. Written images get the key's relative path and CRC32, such as `image_path` and `image_crc32`.
+    fn add(&self, json: &mut Value, embedded_key: &str, key: &str, name: String, bytes: &[u8]) {
+        match self.output {
+            ImageOutput::Embedded => json[embedded_key] = base64::encode(bytes).into(),
+            ImageOutput::File => {
+                let mut file = File::create(format!("{}{}", self.path, name)).unwrap();
+                file.write_all(bytes).unwrap();
+
+                json[format!("{}_path", key)] = name.into();
+                json[format!("{}_crc32", key)] = format!("{:08x}", crc32fast::hash(bytes)).into();
+            }
+        }
+    }
+
+    /// Adds a page's PNG, along with its texture container if it has one.
+    fn add_page(
+        &self,
+        json: &mut Value,
+        page: &internal_sheet::Page,
+        name: String,
+        texture: Option<internal_sheet::Texture>,
+    ) {
+        self.add(
+            json,
+            "image_png_bytes_b64",
+            "image",
+            format!("{}.png", name),
+            &page.image_png_bytes,
+        );
+
+        if let Some(texture) = texture {
+            self.add(
+                json,
+                "texture_bytes_b64",
+                "texture",
+                format!("{}.{}", name, texture.format.name()),
+                &page.texture_bytes,
+            );
+        }
+    }
+
+    fn pages_json(
+        &self,
+        pages: &[internal_sheet::Page],
+        suffix: &str,
+        texture: Option<internal_sheet::Texture>,
+    ) -> Value {
+        pages
+            .iter()
+            .enumerate()
+            .map(|(page_idx, page)| {
+                let mut page_json = json!({
+                    "width_px": page.width_px,
+                    "height_px": page.height_px,
+                });
+
+                let name = page_file_name(self.file_name, suffix, page_idx, pages.len());
+                self.add_page(&mut page_json, page, name, texture);
+
+                page_json
+            })
+            .collect()
+    }
+}
+
+/// Adds the fields the builder sheet format doesn't cover.
+/// Clients ignore fields they don't know about, so older ones still read the first page.
+fn extend_json(json: &mut Value, sprite_sheet: &internal_sheet::SpriteSheet, images: &Images) {
+    let texture = sprite_sheet.texture;
+
+    // The builder sheet format always embeds the first page, so replace it with however images are output
+    if let Some(json) = json.as_object_mut() {
+        json.remove("image_png_bytes_b64");
+    }
+
+    let name = page_file_name(images.file_name, "", 0, sprite_sheet.pages.len());
+    images.add_page(json, &sprite_sheet.pages[0], name, texture);
+
+    if let Some(texture) = texture {
+        json["texture"] = json!({
+            "format": texture.format.name(),
+            "compression": texture.compression.name(),
+            "mipmaps": texture.mipmaps,
+        });
+    }
+
+    if sprite_sheet.pages.len() > 1 {
+        json["pages"] = images.pages_json(&sprite_sheet.pages, "", texture);
+    }
+
+    // Extra passes share the layout of the pages, so their pages are listed in the same order
+    if !sprite_sheet.layers.is_empty() {
+        json["layers"] = sprite_sheet
+            .layers
+            .iter()
+            .map(|layer| {
+                let suffix = format!("_{}", layer.name);
+
+                json!({
+                    "name": layer.name,
+                    "pages": images.pages_json(&layer.pages, &suffix, texture),
+                })
+            })
+            .collect();
+    }
+
+    if let Some(team_mask) = &sprite_sheet.team_mask {
+        let source = match team_mask.source {
+            internal_sheet::TeamMaskSource::Pass => "pass",
+            internal_sheet::TeamMaskSource::ColorKey => "color_key",
+        };
+
+        json["team_mask"] = json!({
+            "layer": team_mask.layer,
+            "channel": "r",
+            "source": source,
+        });
+
+        if let Some(lut) = &team_mask.lut {
+            json["team_mask"]["lut"] = json!({
+                "width_px": lut.width_px,
+                "height_px": lut.height_px,
+                "team_colors": team_mask
+                    .team_colors
+                    .iter()
+                    .map(|[r, g, b]| format!("{:02x}{:02x}{:02x}", r, g, b))
+                    .collect::<Vec<String>>(),
+            });
+
+            let name = format!("{}_team_lut", images.file_name);
+            images.add_page(&mut json["team_mask"]["lut"], lut, name, None);
+        }
+    }
+
+    if let Some(shadow_sheet) = &sprite_sheet.shadow_sheet {
+        json["shadow_sheet"] = json!(shadow_sheet);
+    }
+    if let Some(shadow_of) = &sprite_sheet.shadow_of {
+        json["shadow_of"] = json!(shadow_of);
+    }
+
+    json["premultiplied_alpha"] = sprite_sheet.premultiplied_alpha.into();
+
+    if let Some(grid) = sprite_sheet.grid {
+        json["grid"] = json!({
+            "cell_w_px": grid.cell_w_px,
+            "cell_h_px": grid.cell_h_px,
+            "columns": grid.columns,
+            "rows": grid.rows,
+        });
+    }
+
+    let animations = json["animations"].as_array_mut().unwrap();
+    for (a_json, a) in animations.iter_mut().zip(sprite_sheet.animations.iter()) {
+        a_json["page"] = a.page.into();
+        a_json["fps"] = a.fps.into();
+        a_json["frame_step"] = a.frame_step.into();
+        a_json["loop"] = a.looping.into();
+
+        let directions = a_json["directional_animations"].as_array_mut().unwrap();
+        for (d_json, d) in directions.iter_mut().zip(a.directional_animations.iter()) {
+            d_json["page"] = d.page.into();
+
+            let frames = d_json["frames"].as_array_mut().unwrap();
+            for (f_json, f) in frames.iter_mut().zip(d.frames.iter()) {
+                f_json["page"] = f.page.into();
+                f_json["duration_ms"] = f.duration_ms.into();
+                f_json["rotated"] = f.rotated.into();
+                f_json["empty"] = f.empty.into();
+
+                // The trimmed rect, in pixels from the top left of the full render
+                f_json["source_w_px"] = f.source_w_px.into();
+                f_json["source_h_px"] = f.source_h_px.into();
+                f_json["trim_x_px"] = f.offset_x_px.into();
+                f_json["trim_y_px"] = f.offset_y_px.into();
+                f_json["trim_w_px"] = f.width_px.into();
+                f_json["trim_h_px"] = f.height_px.into();
+
+                // Pivots are relative to the top left of the trimmed frame
+                if let Some((x, y)) = f.pivot_px {
+                    f_json["pivot_x_px"] = (x - f.offset_x_px as f32).into();
+                    f_json["pivot_y_px"] = (y - f.offset_y_px as f32).into();
+                }
+
+                if let Some(cell) = f.grid_cell {
+                    f_json["grid_column"] = cell.column.into();
+                    f_json["grid_row"] = cell.row.into();
+                }
+            }
+        }
+    }
+}
+
+fn map_internal_to_external(sprite_sheet: &internal_sheet::SpriteSheet) -> SpriteSheet {
+    // The builder sheet format only has a single image, so the first page goes there
+    let first_page = &sprite_sheet.pages[0];
+    let image_png_bytes = &first_page.image_png_bytes;
+    let width_px = first_page.width_px;
+    let height_px = first_page.height_px;
+    let animations = map_animations(sprite_sheet);
 
     SpriteSheet::new(image_png_bytes, width_px, height_px, animations)
 }
 
 fn map_animations(sprite_sheet: &internal_sheet::SpriteSheet) -> Vec<Animation> {
     let ctx = Ctx {
-        sheet_w_px_f32: sprite_sheet.width_px as f32,
-        sheet_h_px_f32: sprite_sheet.height_px as f32,
+        page_sizes_px_f32: sprite_sheet
+            .pages
+            .iter()
+            .map(|p| (p.width_px as f32, p.height_px as f32))
+            .collect(),
     };
 
     let animations = sprite_sheet
         .animations
         .iter()
         .map(|a| {
+            let (sheet_w_px_f32, sheet_h_px_f32) = ctx.page_size(a.page);
             let name = a.name.clone();
             let start_x_px = a.start_x_px;
             let start_y_px = a.start_y_px;
-            let start_x_normalized = start_x_px as f32 / sprite_sheet.width_px as f32;
-            let start_y_normalized = start_y_px as f32 / sprite_sheet.height_px as f32;
+            let start_x_normalized = start_x_px as f32 / sheet_w_px_f32;
+            let start_y_normalized = start_y_px as f32 / sheet_h_px_f32;
 
-            let directional_animations =
-                map_directional_animations(&ctx, start_x_px, start_y_px, &a);
+            let directional_animations = map_directional_animations(&ctx, &a);
 
             Animation::new(
                 directional_animations,
@@ -61,32 +302,20 @@ fn map_animations(sprite_sheet: &internal_sheet::SpriteSheet) -> Vec<Animation>
 
 fn map_directional_animations(
     ctx: &Ctx,
-    start_x_px: u32,
-    start_y_px: u32,
     animation: &internal_sheet::Animation,
 ) -> Vec<DirectionalAnimation> {
-    let start_x_px = start_x_px;
-    let mut start_y_px = start_y_px;
-
     let directions = animation
         .directional_animations
         .iter()
         .enumerate()
         .map(|(direction, d)| {
-            let d_start_x_px = start_x_px;
-            let d_start_y_px = start_y_px;
-            let d_start_x_normalized = d_start_x_px as f32 / ctx.sheet_w_px_f32;
-            let d_start_y_normalized = d_start_y_px as f32 / ctx.sheet_h_px_f32;
-
-            let frames = map_frames(ctx, d_start_x_px, d_start_y_px, &d.frames);
+            let (sheet_w_px_f32, sheet_h_px_f32) = ctx.page_size(d.page);
+            let d_start_x_px = d.start_x_px;
+            let d_start_y_px = d.start_y_px;
+            let d_start_x_normalized = d_start_x_px as f32 / sheet_w_px_f32;
+            let d_start_y_normalized = d_start_y_px as f32 / sheet_h_px_f32;
 
-            let mut mapped_h_px = 0;
-
-            for f in frames.iter() {
-                mapped_h_px = mapped_h_px.max(f.height_px());
-            }
-
-            start_y_px += mapped_h_px;
+            let frames = map_frames(ctx, &d.frames);
 
             DirectionalAnimation::new(
                 frames,
@@ -103,36 +332,33 @@ fn map_directional_animations(
 }
 
 struct Ctx {
-    sheet_w_px_f32: f32,
-    sheet_h_px_f32: f32,
+    page_sizes_px_f32: Vec<(f32, f32)>,
 }
 
-fn map_frames(
-    ctx: &Ctx,
-    start_x_px: u32,
-    start_y_px: u32,
-    frames: &Vec<internal_sheet::Frame>,
-) -> Vec<Frame> {
-    let mut start_x_px = start_x_px;
-    let start_y_normalized = start_y_px as f32 / ctx.sheet_h_px_f32;
+impl Ctx {
+    /// Returns the size of the given page. Normalized values are relative to the page the item is on.
+    fn page_size(&self, page: u32) -> (f32, f32) {
+        self.page_sizes_px_f32[page as usize]
+    }
+}
 
+fn map_frames(ctx: &Ctx, frames: &Vec<internal_sheet::Frame>) -> Vec<Frame> {
     let frames = frames
         .iter()
         .map(|f| {
+            let (sheet_w_px_f32, sheet_h_px_f32) = ctx.page_size(f.page);
             let width_px = f.width_px;
             let height_px = f.height_px;
-            let width_normalized = width_px as f32 / ctx.sheet_w_px_f32;
-            let height_normalized = height_px as f32 / ctx.sheet_h_px_f32;
+            let width_normalized = width_px as f32 / sheet_w_px_f32;
+            let height_normalized = height_px as f32 / sheet_h_px_f32;
             let offset_x_px = f.offset_x_px;
             let offset_y_px = f.offset_y_px;
-            let offset_x_normalized = offset_x_px as f32 / ctx.sheet_w_px_f32;
-            let offset_y_normalized = offset_y_px as f32 / ctx.sheet_h_px_f32;
-            let f_start_x_px = start_x_px;
-            let f_start_y_px = start_y_px;
-            let f_start_x_normalized = start_x_px as f32 / ctx.sheet_w_px_f32;
-            let f_start_y_normalized = start_y_normalized;
-
-            start_x_px += width_px;
+            let offset_x_normalized = offset_x_px as f32 / sheet_w_px_f32;
+            let offset_y_normalized = offset_y_px as f32 / sheet_h_px_f32;
+            let f_start_x_px = f.start_x_px;
+            let f_start_y_px = f.start_y_px;
+            let f_start_x_normalized = f_start_x_px as f32 / sheet_w_px_f32;
+            let f_start_y_normalized = f_start_y_px as f32 / sheet_h_px_f32;
 
             Frame::new(
                 width_px,
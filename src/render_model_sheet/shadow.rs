@@ -0,0 +1,269 @@
+use super::crop;
+use crate::cfg::CropPolicy;
+use crate::internal_sheet::{Animation, DirectionalAnimation, Frame};
+use crate::{Cfg, FrameImgs, Render};
+use benchy::Benchy;
+use image::{DynamicImage, GenericImage, Rgba, RgbaImage};
+
+/// How many times the box blur is repeated, which is close enough to a gaussian.
+const BLUR_PASSES: u32 = 3;
+
+/// Makes a drop shadow frame for every rendered frame, by projecting its silhouette onto the ground and blurring it.
+/// Shadow frames keep the render size and pivot of their frame, so both can be drawn at the same position.
+/// Anything projected or blurred past the edge of the render is cut off.
+/// Like frames, shadows that share bounds are made again once every shadow's bounds are known.
+pub fn execute(renders: &[Render], cfg: &Cfg) -> Vec<Render> {
+    Benchy::time("shadow");
+
+    let shadows: Vec<Render> = renders
+        .iter()
+        .map(|(animation, imgs)| shadow_animation(animation, imgs, cfg, None))
+        .collect();
+
+    match cfg.crop {
+        CropPolicy::Frame => shadows,
+        CropPolicy::Direction | CropPolicy::Animation | CropPolicy::Sheet => {
+            let mut shared: Vec<Animation> = shadows.into_iter().map(|(a, _)| a).collect();
+            crop::execute(&mut shared, cfg.crop);
+
+            renders
+                .iter()
+                .zip(shared.iter())
+                .map(|((animation, imgs), shared)| {
+                    shadow_animation(animation, imgs, cfg, Some(shared))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Makes the shadow of each frame of the animation, cropped to its own bounds or to the bounds it shares in the
+/// given shadow animation.
+fn shadow_animation(
+    animation: &Animation,
+    imgs: &[Vec<FrameImgs>],
+    cfg: &Cfg,
+    shared: Option<&Animation>,
+) -> Render {
+    let mut shadow_animation = Animation {
+        start_x_px: 0,
+        start_y_px: 0,
+        name: animation.name.clone(),
+        fps: animation.fps,
+        frame_step: animation.frame_step,
+        looping: animation.looping,
+        page: 0,
+        directional_animations: vec![],
+    };
+    let mut shadow_imgs = vec![];
+
+    for (idx, (direction, imgs)) in animation
+        .directional_animations
+        .iter()
+        .zip(imgs)
+        .enumerate()
+    {
+        let shared = shared.map(|a| &a.directional_animations[idx].frames);
+
+        let (frames, imgs): (Vec<Frame>, Vec<FrameImgs>) = direction
+            .frames
+            .iter()
+            .zip(imgs)
+            .enumerate()
+            .map(|(frame_idx, (frame, imgs))| {
+                shadow_frame(frame, imgs, cfg, shared.map(|s| &s[frame_idx]))
+            })
+            .unzip();
+
+        shadow_animation
+            .directional_animations
+            .push(DirectionalAnimation {
+                start_x_px: 0,
+                start_y_px: 0,
+                page: 0,
+                direction: direction.direction,
+                frames,
+            });
+        shadow_imgs.push(imgs);
+    }
+
+    (shadow_animation, shadow_imgs)
+}
+
+fn shadow_frame(
+    frame: &Frame,
+    imgs: &FrameImgs,
+    cfg: &Cfg,
+    shared: Option<&Frame>,
+) -> (Frame, FrameImgs) {
+    let mut shadow_frame = Frame {
+        duration_ms: frame.duration_ms,
+        empty: true,
+        grid_cell: None,
+        height_px: 0,
+        width_px: 0,
+        offset_x_px: 0,
+        offset_y_px: 0,
+        page: 0,
+        pivot_px: frame.pivot_px,
+        rotated: false,
+        source_w_px: frame.source_w_px,
+        source_h_px: frame.source_h_px,
+        start_x_px: 0,
+        start_y_px: 0,
+    };
+    let empty_imgs = || FrameImgs {
+        color: DynamicImage::new_rgba8(0, 0),
+        passes: vec![],
+    };
+
+    if frame.empty {
+        return (shadow_frame, empty_imgs());
+    }
+
+    // Put the frame back where it was in the render
+    let mut silhouette = RgbaImage::new(frame.source_w_px, frame.source_h_px);
+    silhouette
+        .copy_from(&imgs.color, frame.offset_x_px, frame.offset_y_px)
+        .unwrap();
+
+    // Shadows fall onto the pivot, or the bottom of the frame without one
+    let ground_y = match frame.pivot_px {
+        Some((_, y)) => y,
+        None => (frame.offset_y_px + frame.height_px) as f32,
+    };
+
+    let mut alpha = project(&silhouette, ground_y, cfg);
+    for _ in 0..BLUR_PASSES {
+        alpha = blur(
+            &alpha,
+            frame.source_w_px,
+            frame.source_h_px,
+            cfg.shadow_blur,
+        );
+    }
+
+    // Tint it, then crop to what's visible
+    let [r, g, b, opacity] = cfg.shadow_color;
+    let shadow = RgbaImage::from_fn(frame.source_w_px, frame.source_h_px, |x, y| {
+        let a = alpha[(y * frame.source_w_px + x) as usize] * opacity as f32 / 255.0;
+        Rgba([r, g, b, a.round() as u8])
+    });
+
+    let bounds = shadow
+        .enumerate_pixels()
+        .filter(|(_, _, p)| p[3] > cfg.alpha_threshold)
+        .fold(None, |bounds, (x, y, _)| match bounds {
+            Some((x0, y0, x1, y1)) => Some((x.min(x0), y.min(y0), x.max(x1), y.max(y1))),
+            None => Some((x, y, x, y)),
+        });
+
+    let (start_x, start_y, end_x, end_y) = match bounds {
+        Some(bounds) => bounds,
+        None => return (shadow_frame, empty_imgs()),
+    };
+
+    // The same 1px buffer frames get
+    let start_x = start_x.saturating_sub(1);
+    let start_y = start_y.saturating_sub(1);
+    let end_x = (end_x + 2).min(frame.source_w_px);
+    let end_y = (end_y + 2).min(frame.source_h_px);
+
+    shadow_frame.empty = false;
+    shadow_frame.offset_x_px = start_x;
+    shadow_frame.offset_y_px = start_y;
+    shadow_frame.width_px = end_x - start_x;
+    shadow_frame.height_px = end_y - start_y;
+
+    // Shadows sharing bounds with others are cropped to those instead
+    if let Some(shared) = shared {
+        shadow_frame.offset_x_px = shared.offset_x_px;
+        shadow_frame.offset_y_px = shared.offset_y_px;
+        shadow_frame.width_px = shared.width_px;
+        shadow_frame.height_px = shared.height_px;
+    }
+
+    let color = DynamicImage::ImageRgba8(shadow).crop(
+        shadow_frame.offset_x_px,
+        shadow_frame.offset_y_px,
+        shadow_frame.width_px,
+        shadow_frame.height_px,
+    );
+
+    (
+        shadow_frame,
+        FrameImgs {
+            color,
+            passes: vec![],
+        },
+    )
+}
+
+/// Flattens the silhouette onto the ground line through the pivot, returning the alpha of each pixel.
+/// Each pixel above the ground is pulled towards it by the shadow length, and shifted sideways by the skew.
+fn project(silhouette: &RgbaImage, ground_y: f32, cfg: &Cfg) -> Vec<f32> {
+    let (w, h) = silhouette.dimensions();
+    let length = cfg.shadow_length as f32 / 100.0;
+    let skew = cfg.shadow_skew as f32 / 100.0;
+
+    let mut alpha = vec![0.0; (w * h) as usize];
+    if length <= 0.0 {
+        return alpha;
+    }
+
+    // Map each shadow pixel back to the silhouette, so there are no gaps when stretching
+    for y in 0..h {
+        for x in 0..w {
+            let height = ground_y - (y as f32 + 0.5);
+            let source_y = ground_y - height / length;
+            let source_x = x as f32 + 0.5 - skew * height / length;
+
+            if source_x < 0.0 || source_y < 0.0 {
+                continue;
+            }
+
+            let (source_x, source_y) = (source_x as u32, source_y as u32);
+            if source_x < w && source_y < h {
+                alpha[(y * w + x) as usize] = silhouette.get_pixel(source_x, source_y)[3] as f32;
+            }
+        }
+    }
+
+    alpha
+}
+
+/// Box blurs the alpha horizontally, then vertically.
+fn blur(alpha: &[f32], w: u32, h: u32, radius: u32) -> Vec<f32> {
+    if radius == 0 {
+        return alpha.to_vec();
+    }
+
+    let radius = radius as i64;
+    let (w, h) = (w as i64, h as i64);
+    let average = |get: &dyn Fn(i64) -> f32, len: i64, i: i64| {
+        let sum: f32 = (i - radius..=i + radius)
+            .filter(|j| *j >= 0 && *j < len)
+            .map(get)
+            .sum();
+
+        sum / (radius * 2 + 1) as f32
+    };
+
+    let mut horizontal = vec![0.0; alpha.len()];
+    for y in 0..h {
+        for x in 0..w {
+            let get = |j: i64| alpha[(y * w + j) as usize];
+            horizontal[(y * w + x) as usize] = average(&get, w, x);
+        }
+    }
+
+    let mut vertical = vec![0.0; alpha.len()];
+    for y in 0..h {
+        for x in 0..w {
+            let get = |j: i64| horizontal[(j * w + x) as usize];
+            vertical[(y * w + x) as usize] = average(&get, h, y);
+        }
+    }
+
+    vertical
+}
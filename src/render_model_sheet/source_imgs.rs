@@ -2,6 +2,7 @@ use benchy::Benchy;
 use walkdir::WalkDir;
 
 use super::sorted_map::SortedMap;
+use crate::cfg::PassKind;
 
 #[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Ord)]
 pub struct ImgToRender {
@@ -9,10 +10,14 @@ pub struct ImgToRender {
     pub direction: u32,
     pub frame: u32,
     pub path: String,
+    /// The file with the frame's pivot, if Blender wrote one.
+    pub pivot_path: Option<String>,
+    /// The files with the frame's extra passes that Blender rendered.
+    pub pass_paths: Vec<(PassKind, String)>,
 }
 
 impl ImgToRender {
-    pub fn new(entry: &walkdir::DirEntry) -> Option<Self> {
+    pub fn new(entry: &walkdir::DirEntry, passes: &[PassKind]) -> Option<Self> {
         let extension = ".png";
 
         let file_name = String::from(entry.file_name().to_string_lossy());
@@ -51,17 +56,51 @@ impl ImgToRender {
 
         let path = entry.path().to_str().unwrap_or_default().to_string();
 
+        let pivot_path = entry.path().with_extension("pivot");
+        let pivot_path = if pivot_path.exists() {
+            Some(pivot_path.to_str().unwrap_or_default().to_string())
+        } else {
+            None
+        };
+
+        // Extra passes are named after the frame, so they don't get picked up as frames themselves
+        let pass_paths = passes
+            .iter()
+            .map(|pass| {
+                let pass_path = entry.path().with_file_name(format!(
+                    "{}_ESCAPED{}{}",
+                    parsed_name,
+                    pass.name(),
+                    extension
+                ));
+
+                // Every layer needs an image of every frame, or its pages would have blank regions
+                if !pass_path.exists() {
+                    panic!(
+                        "The {} pass for '{}' is missing, as '{}' doesn't exist.",
+                        pass.name(),
+                        path,
+                        pass_path.to_string_lossy()
+                    );
+                }
+
+                (*pass, pass_path.to_str().unwrap_or_default().to_string())
+            })
+            .collect();
+
         Some(Self {
             animation,
             direction,
             frame,
             path,
+            pivot_path,
+            pass_paths,
         })
     }
 }
 
 /// Sources images for a single spritesheet.
-pub fn execute<'a>(path: &'a str) -> SortedMap<String, Vec<ImgToRender>> {
+pub fn execute<'a>(path: &'a str, passes: &[PassKind]) -> SortedMap<String, Vec<ImgToRender>> {
     Benchy::time("source_imgs");
 
     // Make a list of images to render
@@ -74,7 +113,7 @@ pub fn execute<'a>(path: &'a str) -> SortedMap<String, Vec<ImgToRender>> {
         .filter_map(Result::ok)
         .filter(|e| !e.file_type().is_dir())
     {
-        if let Some(img) = ImgToRender::new(&entry) {
+        if let Some(img) = ImgToRender::new(&entry, passes) {
             if let Some(directions) = imgs.get_mut(&img.animation) {
                 directions.push(img);
             } else {
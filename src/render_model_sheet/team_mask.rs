@@ -0,0 +1,88 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+/// Pixels less saturated than this are grays, so they're never part of the team color.
+const MIN_SATURATION: f32 = 0.25;
+
+/// The brightness team colors are shaded relative to when there's no key color.
+const DEFAULT_KEY_LUMINANCE: f32 = 128.0;
+
+/// Marks the pixels of the color pass with a hue close to the key color.
+/// Recolorable pixels are white and the rest are black, keeping the alpha of the color pass.
+pub fn from_color_key(img: &DynamicImage, key: [u8; 3], tolerance_degs: u32) -> DynamicImage {
+    let (key_hue, _) = hue_saturation(key);
+
+    let mask = RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        let (hue, saturation) = hue_saturation([p[0], p[1], p[2]]);
+
+        // Hues wrap around
+        let distance = (hue - key_hue).abs();
+        let distance = distance.min(360.0 - distance);
+
+        let value = if saturation >= MIN_SATURATION && distance <= tolerance_degs as f32 {
+            255
+        } else {
+            0
+        };
+
+        Rgba([value, value, value, p[3]])
+    });
+
+    DynamicImage::ImageRgba8(mask)
+}
+
+/// Makes a lookup texture with a row for each team color, for palette swap shaders.
+/// Columns are the brightness of the masked pixel, from 0 to 255. The key color's brightness maps to the team color,
+/// with darker pixels shading towards black and brighter ones towards white.
+pub fn lut(team_colors: &[[u8; 3]], key: Option<[u8; 3]>) -> RgbaImage {
+    let key_luminance = key.map(luminance).unwrap_or(DEFAULT_KEY_LUMINANCE).max(1.0);
+
+    RgbaImage::from_fn(256, team_colors.len() as u32, |x, y| {
+        let color = team_colors[y as usize];
+        let shade = x as f32 / key_luminance;
+
+        let channel = |c: u8| {
+            let c = c as f32;
+            let shaded = if shade <= 1.0 {
+                c * shade
+            } else {
+                let towards_white = (x as f32 - key_luminance) / (255.0 - key_luminance).max(1.0);
+                c + (255.0 - c) * towards_white
+            };
+
+            shaded.round().clamp(0.0, 255.0) as u8
+        };
+
+        Rgba([channel(color[0]), channel(color[1]), channel(color[2]), 255])
+    })
+}
+
+fn luminance(color: [u8; 3]) -> f32 {
+    0.299 * color[0] as f32 + 0.587 * color[1] as f32 + 0.114 * color[2] as f32
+}
+
+/// Returns the hue in degrees and the saturation from 0 to 1.
+fn hue_saturation(color: [u8; 3]) -> (f32, f32) {
+    let [r, g, b] = [
+        color[0] as f32 / 255.0,
+        color[1] as f32 / 255.0,
+        color[2] as f32 / 255.0,
+    ];
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    if delta == 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let hue = if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+
+    (hue, delta / max)
+}
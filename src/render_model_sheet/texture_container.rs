@@ -0,0 +1,335 @@
+use crate::cfg::{BlockCompression, TextureFormat};
+
+const DDS_MAGIC: &'static [u8; 4] = b"DDS ";
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// A block compressed page, with each of its mip levels.
+pub struct CompressedTexture {
+    pub width_px: u32,
+    pub height_px: u32,
+    pub compression: BlockCompression,
+    /// Colors are sRGB, while data like normals are linear.
+    pub srgb: bool,
+    pub premultiplied_alpha: bool,
+    /// The compressed blocks of each mip level, from largest to smallest.
+    pub levels: Vec<Vec<u8>>,
+}
+
+/// Wraps the texture in the given container.
+pub fn execute(texture: &CompressedTexture, format: TextureFormat) -> Vec<u8> {
+    match format {
+        TextureFormat::Png => panic!("PNGs aren't a texture container."),
+        TextureFormat::Dds => dds(texture),
+        TextureFormat::Ktx2 => ktx2(texture),
+    }
+}
+
+/// Writes a DDS, using the DX10 header so the format can be sRGB and BC7.
+fn dds(texture: &CompressedTexture) -> Vec<u8> {
+    let has_mips = texture.levels.len() > 1;
+
+    // Caps, height, width, pixel format and linear size, along with the mip count if there are mips
+    let mut flags = 0x1 | 0x2 | 0x4 | 0x1000 | 0x80000;
+    // Texture, along with complex and mipmap if there are mips
+    let mut caps = 0x1000;
+    if has_mips {
+        flags |= 0x20000;
+        caps |= 0x8 | 0x400000;
+    }
+
+    let dxgi_format = match (texture.compression, texture.srgb) {
+        (BlockCompression::Bc1, false) => 71,
+        (BlockCompression::Bc1, true) => 72,
+        (BlockCompression::Bc3, false) => 77,
+        (BlockCompression::Bc3, true) => 78,
+        (BlockCompression::Bc7, false) => 98,
+        (BlockCompression::Bc7, true) => 99,
+    };
+
+    let alpha_mode = if texture.premultiplied_alpha { 2 } else { 1 };
+
+    let mut bytes = DDS_MAGIC.to_vec();
+    let mut push = |value: u32| bytes.extend_from_slice(&value.to_le_bytes());
+
+    // Header
+    push(124);
+    push(flags);
+    push(texture.height_px);
+    push(texture.width_px);
+    push(texture.levels[0].len() as u32);
+    push(0);
+    push(texture.levels.len() as u32);
+    for _ in 0..11 {
+        push(0);
+    }
+
+    // Pixel format, deferring to the DX10 header
+    push(32);
+    push(0x4);
+    push(u32::from_le_bytes(*b"DX10"));
+    for _ in 0..5 {
+        push(0);
+    }
+
+    push(caps);
+    for _ in 0..4 {
+        push(0);
+    }
+
+    // DX10 header, as a single 2D texture
+    push(dxgi_format);
+    push(3);
+    push(0);
+    push(1);
+    push(alpha_mode);
+
+    for level in texture.levels.iter() {
+        bytes.extend_from_slice(level);
+    }
+
+    bytes
+}
+
+/// Writes a KTX2, with the basic data format descriptor it requires.
+fn ktx2(texture: &CompressedTexture) -> Vec<u8> {
+    let vk_format: u32 = match (texture.compression, texture.srgb) {
+        (BlockCompression::Bc1, false) => 133,
+        (BlockCompression::Bc1, true) => 134,
+        (BlockCompression::Bc3, false) => 137,
+        (BlockCompression::Bc3, true) => 138,
+        (BlockCompression::Bc7, false) => 145,
+        (BlockCompression::Bc7, true) => 146,
+    };
+
+    let dfd = data_format_descriptor(texture);
+    let num_levels = texture.levels.len();
+
+    // The identifier, header and index come first, then the level index and descriptor
+    let level_index_offset = 80;
+    let dfd_offset = level_index_offset + num_levels * 24;
+
+    // Levels are stored smallest first, each aligned to a block
+    let alignment = texture.compression.block_bytes();
+    let mut level_offsets = vec![0; num_levels];
+    let mut end = dfd_offset + dfd.len();
+    for (idx, level) in texture.levels.iter().enumerate().rev() {
+        end = end.div_ceil(alignment) * alignment;
+        level_offsets[idx] = end;
+        end += level.len();
+    }
+
+    let mut bytes = KTX2_IDENTIFIER.to_vec();
+    {
+        let mut push = |value: u32| bytes.extend_from_slice(&value.to_le_bytes());
+
+        push(vk_format);
+        // Type size, which is 1 for compressed formats
+        push(1);
+        push(texture.width_px);
+        push(texture.height_px);
+        // Depth and layers, as it's a single 2D texture
+        push(0);
+        push(0);
+        // Faces
+        push(1);
+        push(num_levels as u32);
+        // No supercompression
+        push(0);
+
+        push(dfd_offset as u32);
+        push(dfd.len() as u32);
+        // No key value data
+        push(0);
+        push(0);
+    }
+
+    // No supercompression global data
+    bytes.extend_from_slice(&0u64.to_le_bytes());
+    bytes.extend_from_slice(&0u64.to_le_bytes());
+
+    for (level, offset) in texture.levels.iter().zip(level_offsets.iter()) {
+        bytes.extend_from_slice(&(*offset as u64).to_le_bytes());
+        bytes.extend_from_slice(&(level.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(level.len() as u64).to_le_bytes());
+    }
+
+    bytes.extend_from_slice(&dfd);
+
+    for (idx, level) in texture.levels.iter().enumerate().rev() {
+        bytes.resize(level_offsets[idx], 0);
+        bytes.extend_from_slice(level);
+    }
+
+    bytes
+}
+
+/// Describes the compressed blocks, as a Khronos basic data format descriptor.
+fn data_format_descriptor(texture: &CompressedTexture) -> Vec<u8> {
+    // sRGB alpha is still linear
+    let linear_alpha = if texture.srgb { 0x10 } else { 0 };
+
+    // The color model, then the (channel, bit offset, bit length) of each sample
+    let (color_model, samples): (u8, Vec<(u8, u16, u8)>) = match texture.compression {
+        BlockCompression::Bc1 => (128, vec![(1, 0, 64)]),
+        BlockCompression::Bc3 => (130, vec![(15 | linear_alpha, 0, 64), (0, 64, 64)]),
+        BlockCompression::Bc7 => (134, vec![(0, 0, 128)]),
+    };
+
+    let block_size = 24 + 16 * samples.len();
+
+    let mut dfd = vec![];
+    dfd.extend_from_slice(&((4 + block_size) as u32).to_le_bytes());
+
+    // Khronos vendor and basic descriptor type, then version 1.3
+    dfd.extend_from_slice(&0u32.to_le_bytes());
+    dfd.extend_from_slice(&2u16.to_le_bytes());
+    dfd.extend_from_slice(&(block_size as u16).to_le_bytes());
+
+    let transfer_function = if texture.srgb { 2 } else { 1 };
+    let premultiplied = if texture.premultiplied_alpha { 1 } else { 0 };
+
+    // BT.709 primaries, then 4x4x1x1 blocks
+    dfd.extend_from_slice(&[color_model, 1, transfer_function, premultiplied]);
+    dfd.extend_from_slice(&[3, 3, 0, 0]);
+    dfd.extend_from_slice(&[texture.compression.block_bytes() as u8, 0, 0, 0, 0, 0, 0, 0]);
+
+    for (channel, bit_offset, bit_length) in samples {
+        dfd.extend_from_slice(&bit_offset.to_le_bytes());
+        dfd.extend_from_slice(&[bit_length - 1, channel]);
+        dfd.extend_from_slice(&[0, 0, 0, 0]);
+        dfd.extend_from_slice(&0u32.to_le_bytes());
+        dfd.extend_from_slice(&u32::MAX.to_le_bytes());
+    }
+
+    dfd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An 8x4 texture with two mip levels, each filled with a different byte.
+    fn texture(compression: BlockCompression, srgb: bool) -> CompressedTexture {
+        let block_bytes = compression.block_bytes();
+
+        CompressedTexture {
+            width_px: 8,
+            height_px: 4,
+            compression,
+            srgb,
+            premultiplied_alpha: false,
+            levels: vec![vec![1; block_bytes * 2], vec![2; block_bytes]],
+        }
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        let mut value = [0u8; 4];
+        value.copy_from_slice(&bytes[offset..offset + 4]);
+        u32::from_le_bytes(value)
+    }
+
+    fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+        let mut value = [0u8; 8];
+        value.copy_from_slice(&bytes[offset..offset + 8]);
+        u64::from_le_bytes(value)
+    }
+
+    #[test]
+    fn dds_header() {
+        let bytes = execute(&texture(BlockCompression::Bc1, true), TextureFormat::Dds);
+
+        assert_eq!(DDS_MAGIC, &bytes[0..4]);
+        assert_eq!(124, u32_at(&bytes, 4));
+        assert_eq!(4, u32_at(&bytes, 12));
+        assert_eq!(8, u32_at(&bytes, 16));
+        // Linear size is the size of the first level
+        assert_eq!(16, u32_at(&bytes, 20));
+        assert_eq!(2, u32_at(&bytes, 28));
+        assert_eq!(*b"DX10", bytes[84..88]);
+        // Mipmaps set the mip count flag and caps
+        assert_ne!(0, u32_at(&bytes, 8) & 0x20000);
+        assert_ne!(0, u32_at(&bytes, 108) & 0x400000);
+
+        // The DX10 header, then the levels from largest to smallest
+        assert_eq!(72, u32_at(&bytes, 128));
+        assert_eq!(3, u32_at(&bytes, 132));
+        assert_eq!(148 + 24, bytes.len());
+        assert_eq!(vec![1; 16], bytes[148..164].to_vec());
+        assert_eq!(vec![2; 8], bytes[164..172].to_vec());
+    }
+
+    #[test]
+    fn dds_formats() {
+        let formats = [
+            (BlockCompression::Bc1, 71, 72),
+            (BlockCompression::Bc3, 77, 78),
+            (BlockCompression::Bc7, 98, 99),
+        ];
+
+        for (compression, linear, srgb) in formats.iter() {
+            let dds = |is_srgb| {
+                u32_at(
+                    &execute(&texture(*compression, is_srgb), TextureFormat::Dds),
+                    128,
+                )
+            };
+            assert_eq!(*linear, dds(false));
+            assert_eq!(*srgb, dds(true));
+        }
+    }
+
+    #[test]
+    fn ktx2_header() {
+        let texture = texture(BlockCompression::Bc7, false);
+        let bytes = execute(&texture, TextureFormat::Ktx2);
+
+        assert_eq!(KTX2_IDENTIFIER, bytes[0..12]);
+        assert_eq!(145, u32_at(&bytes, 12));
+        assert_eq!(1, u32_at(&bytes, 16));
+        assert_eq!(8, u32_at(&bytes, 20));
+        assert_eq!(4, u32_at(&bytes, 24));
+        assert_eq!(1, u32_at(&bytes, 36));
+        assert_eq!(2, u32_at(&bytes, 40));
+
+        // The descriptor starts with its own size
+        let (dfd_offset, dfd_len) = (u32_at(&bytes, 48) as usize, u32_at(&bytes, 52) as usize);
+        assert_eq!(80 + 2 * 24, dfd_offset);
+        assert_eq!(dfd_len as u32, u32_at(&bytes, dfd_offset));
+
+        // Each level is where the index says, aligned to a block and stored smallest first
+        let mut offsets = vec![];
+        for (idx, level) in texture.levels.iter().enumerate() {
+            let offset = u64_at(&bytes, 80 + idx * 24) as usize;
+            assert_eq!(level.len() as u64, u64_at(&bytes, 80 + idx * 24 + 8));
+            assert_eq!(0, offset % 16);
+            assert!(offset >= dfd_offset + dfd_len);
+            assert_eq!(*level, bytes[offset..offset + level.len()].to_vec());
+            offsets.push(offset);
+        }
+        assert!(offsets[1] < offsets[0]);
+        assert_eq!(offsets[0] + texture.levels[0].len(), bytes.len());
+    }
+
+    #[test]
+    fn ktx2_formats() {
+        let formats = [
+            (BlockCompression::Bc1, 133, 134),
+            (BlockCompression::Bc3, 137, 138),
+            (BlockCompression::Bc7, 145, 146),
+        ];
+
+        for (compression, linear, srgb) in formats.iter() {
+            let ktx2 = |is_srgb| {
+                u32_at(
+                    &execute(&texture(*compression, is_srgb), TextureFormat::Ktx2),
+                    12,
+                )
+            };
+            assert_eq!(*linear, ktx2(false));
+            assert_eq!(*srgb, ktx2(true));
+        }
+    }
+}